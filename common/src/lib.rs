@@ -1,5 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+/// Header sent with every request of the browsers when the e2e runner launched the server, unless
+/// with `--no-test-id-header`, identifying the test and attempt that sent it
+///
+/// Servers that log it, e.g. as `x-e2e-test-id: <id>`, get the lines tagged with the id of a
+/// failing test attached to its result. Untagged lines logged while the test ran are attached as
+/// well, since they are often the consequence of its requests, e.g. panic messages.
+pub const TEST_ID_HEADER: &str = "x-e2e-test-id";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PayloadCookies {
     pub message: String,
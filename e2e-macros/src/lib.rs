@@ -0,0 +1,434 @@
+//! The `#[e2e::test]` and hook attributes, use them through the `e2e` crate which re-exports
+//! them.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    Error, Expr, ExprArray, ExprLit, Ident, ItemFn, Lit, LitStr, Token,
+};
+
+/// Declares an e2e test, with its metadata:
+///
+/// ```ignore
+/// #[e2e::test(browsers = [chromium, firefox], timeout = "30s", tags = ["smoke"])]
+/// async fn login(ctx: Context) -> Result<()> {
+///     ...
+/// }
+/// ```
+///
+/// Every argument is optional:
+///
+/// - `browsers = [...]`: only run the test in these browsers
+/// - `expected_failure = [...]`: browsers the test is known to fail in
+/// - `ignore` or `ignore = "reason"`: don't run the test
+/// - `tags = ["...", ...]`: select the test with `--tags` and `--exclude-tags`
+/// - `timeout = "30s"`: overrides `--test-timeout`, in `ms`, `s` or `m`
+/// - `retries = 2`: overrides `--retries`
+/// - `fixtures = [Type, ...]`: fixtures set up before the test, see `e2e::fixture`
+///
+/// and options of its browser context, over the ones of the run, see `e2e::context_options`:
+///
+/// - `viewport = "375x812"`
+/// - `locale = "fr-FR"`
+/// - `timezone = "Europe/Paris"`
+/// - `geolocation = [48.8566, 2.3522]`, optionally followed by the accuracy in meters
+/// - `color_scheme = "dark"`: `light`, `dark` or `no-preference`
+/// - `user_agent = "..."`
+/// - `permissions = ["geolocation", ...]`
+/// - `headers = [("Name", "value"), ...]`
+/// - `javascript = false`
+///
+/// The test is named after its path without the crate name, e.g. `tests::login`, like libtest
+/// does. The function itself is left as is, and registered to be found by the test runner
+/// without `#[test_case]`.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as Args);
+    let test = parse_macro_input!(item as ItemFn);
+    match expand(args, test) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Declares a hook run once before the tests, or in each browser once it is launched with
+/// `#[e2e::before_all(per_browser)]`:
+///
+/// ```ignore
+/// #[e2e::before_all]
+/// async fn seed(ctx: &FixtureContext<'_>) -> Result<()> {
+///     ...
+/// }
+/// ```
+///
+/// See `e2e::hook` for when hooks run and how their failures are handled.
+#[proc_macro_attribute]
+pub fn before_all(args: TokenStream, item: TokenStream) -> TokenStream {
+    hook("BeforeAll", args, item)
+}
+
+/// Declares a hook run once the tests are done, or in each browser with
+/// `#[e2e::after_all(per_browser)]`, see [`macro@before_all`]
+#[proc_macro_attribute]
+pub fn after_all(args: TokenStream, item: TokenStream) -> TokenStream {
+    hook("AfterAll", args, item)
+}
+
+/// Declares a hook run before each attempt of a test, see [`macro@before_all`]
+#[proc_macro_attribute]
+pub fn before_each(args: TokenStream, item: TokenStream) -> TokenStream {
+    hook("BeforeEach", args, item)
+}
+
+/// Declares a hook run after each attempt of a test, see [`macro@before_all`]
+#[proc_macro_attribute]
+pub fn after_each(args: TokenStream, item: TokenStream) -> TokenStream {
+    hook("AfterEach", args, item)
+}
+
+fn hook(when: &str, args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as Args);
+    let hook = parse_macro_input!(item as ItemFn);
+    match expand_hook(when, args, hook) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+struct Args(Punctuated<Arg, Token![,]>);
+
+struct Arg {
+    name: Ident,
+    value: Option<Expr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Args(Punctuated::parse_terminated(input)?))
+    }
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(Arg { name, value })
+    }
+}
+
+fn expand(args: Args, test: ItemFn) -> syn::Result<TokenStream2> {
+    if test.sig.asyncness.is_none() {
+        return Err(Error::new(
+            test.sig.fn_token.span(),
+            "e2e tests must be `async fn`s taking a `Context`",
+        ));
+    }
+
+    let name = &test.sig.ident;
+    let mut builders = Vec::new();
+    let mut context = Vec::new();
+    for Arg { name: arg, value } in args.0 {
+        let span = arg.span();
+        if let Some(value) = &value {
+            if let Some(option) = context_option(&arg, value)? {
+                context.push(option);
+                continue;
+            }
+        }
+        let builder = match (arg.to_string().as_str(), value) {
+            ("browsers", Some(value)) => {
+                let browsers = browsers(&value)?;
+                quote_spanned!(span=> .only_in(&[#(#browsers),*]))
+            }
+            ("expected_failure", Some(value)) => {
+                let browsers = browsers(&value)?;
+                quote_spanned!(span=> .expected_failure_in(&[#(#browsers),*]))
+            }
+            ("ignore", None) => quote_spanned!(span=> .ignore("")),
+            ("ignore", Some(value)) => {
+                let reason = string(&value)?;
+                quote_spanned!(span=> .ignore(#reason))
+            }
+            ("tags", Some(value)) => {
+                let tags = array(&value)?
+                    .elems
+                    .iter()
+                    .map(string)
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote_spanned!(span=> .tags(&[#(#tags),*]))
+            }
+            ("timeout", Some(value)) => {
+                let millis = duration_millis(&string(&value)?)?;
+                quote_spanned!(span=> .timeout(::std::time::Duration::from_millis(#millis)))
+            }
+            ("retries", Some(value)) => match &value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(retries),
+                    ..
+                }) => quote_spanned!(span=> .retries(#retries)),
+                _ => return Err(Error::new(value.span(), "expected a number of retries")),
+            },
+            ("fixtures", Some(value)) => {
+                let fixtures = array(&value)?.elems.iter().map(|fixture| match fixture {
+                    Expr::Path(path) => Ok(path),
+                    _ => Err(Error::new(fixture.span(), "expected a fixture type")),
+                });
+                let fixtures = fixtures.collect::<syn::Result<Vec<_>>>()?;
+                quote_spanned!(span=> .fixtures(&[#(::e2e::fixture::FixtureRef::of::<#fixtures>()),*]))
+            }
+            (
+                "browsers" | "expected_failure" | "tags" | "timeout" | "retries" | "fixtures"
+                | "viewport" | "locale" | "timezone" | "geolocation" | "color_scheme"
+                | "user_agent" | "permissions" | "headers" | "javascript",
+                None,
+            ) => {
+                return Err(Error::new(span, format!("expected `{} = ...`", arg)));
+            }
+            _ => {
+                return Err(Error::new(
+                    span,
+                    "unknown argument, expected one of `browsers`, `expected_failure`, \
+                     `ignore`, `tags`, `timeout`, `retries`, `fixtures` or a context option",
+                ))
+            }
+        };
+        builders.push(builder);
+    }
+    if !context.is_empty() {
+        builders.push(quote! {
+            .context(::e2e::context_options::ContextOptions::new() #(#context)*)
+        });
+    }
+
+    let path = format!("::{}", name);
+    Ok(quote! {
+        #test
+
+        ::e2e::register!(
+            &::e2e::meta::Test::new(#name)
+                .name(::e2e::meta::without_crate_name(concat!(module_path!(), #path)))
+                #(#builders)*
+        );
+    })
+}
+
+fn expand_hook(when: &str, args: Args, hook: ItemFn) -> syn::Result<TokenStream2> {
+    if hook.sig.asyncness.is_none() {
+        return Err(Error::new(
+            hook.sig.fn_token.span(),
+            "hooks must be `async fn`s taking a `&FixtureContext`",
+        ));
+    }
+
+    let per_browser_allowed = when.ends_with("All");
+    let mut builders = Vec::new();
+    for Arg { name: arg, value } in args.0 {
+        match value {
+            None if arg == "per_browser" && per_browser_allowed => {
+                builders.push(quote_spanned!(arg.span()=> .per_browser()))
+            }
+            _ if per_browser_allowed => {
+                return Err(Error::new(
+                    arg.span(),
+                    "unknown argument, expected `per_browser`",
+                ))
+            }
+            _ => return Err(Error::new(arg.span(), "this hook takes no arguments")),
+        }
+    }
+
+    let name = &hook.sig.ident;
+    let when = Ident::new(when, Span::call_site());
+    let path = format!("::{}", name);
+    Ok(quote! {
+        #hook
+
+        ::e2e::register!(hook
+            ::e2e::hook::Hook::new(
+                ::e2e::hook::When::#when,
+                ::e2e::meta::without_crate_name(concat!(module_path!(), #path)),
+                |ctx| ::std::boxed::Box::pin(#name(ctx)),
+            )
+            #(#builders)*
+        );
+    })
+}
+
+/// The `ContextOptions` builder call for `arg`, if it is a context option
+fn context_option(arg: &Ident, value: &Expr) -> syn::Result<Option<TokenStream2>> {
+    let span = arg.span();
+    let option = match arg.to_string().as_str() {
+        "viewport" => {
+            let viewport = string(value)?;
+            let size = viewport.value();
+            let size = size.split_once('x').and_then(|(width, height)| {
+                Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+            });
+            match size {
+                Some((width, height)) => quote_spanned!(span=> .viewport(#width, #height)),
+                None => {
+                    return Err(Error::new(
+                        viewport.span(),
+                        "expected a viewport such as \"1280x720\"",
+                    ))
+                }
+            }
+        }
+        "locale" | "timezone" | "user_agent" => {
+            let value = string(value)?;
+            quote_spanned!(span=> .#arg(#value))
+        }
+        "geolocation" => {
+            let coordinates: Vec<_> = array(value)?.elems.iter().collect();
+            match coordinates[..] {
+                [latitude, longitude] => quote_spanned!(span=>
+                    .geolocation(::e2e::context_options::Geolocation::new(#latitude, #longitude))
+                ),
+                [latitude, longitude, accuracy] => quote_spanned!(span=>
+                    .geolocation(
+                        ::e2e::context_options::Geolocation::new(#latitude, #longitude)
+                            .accuracy(#accuracy)
+                    )
+                ),
+                _ => {
+                    return Err(Error::new(
+                        value.span(),
+                        "expected `[latitude, longitude]`, optionally followed by the accuracy",
+                    ))
+                }
+            }
+        }
+        "color_scheme" => {
+            let color_scheme = string(value)?;
+            let variant = match color_scheme.value().as_str() {
+                "light" => quote!(Light),
+                "dark" => quote!(Dark),
+                "no-preference" => quote!(NoPreference),
+                _ => {
+                    return Err(Error::new(
+                        color_scheme.span(),
+                        "unknown color scheme, expected light, dark or no-preference",
+                    ))
+                }
+            };
+            quote_spanned!(span=> .color_scheme(::e2e::context_options::ColorScheme::#variant))
+        }
+        "permissions" => {
+            let permissions = array(value)?
+                .elems
+                .iter()
+                .map(string)
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote_spanned!(span=> .permissions(&[#(#permissions),*]))
+        }
+        "headers" => {
+            let headers = &array(value)?.elems;
+            quote_spanned!(span=> .extra_http_headers(&[#headers]))
+        }
+        "javascript" => quote_spanned!(span=> .javascript_enabled(#value)),
+        _ => return Ok(None),
+    };
+    Ok(Some(option))
+}
+
+/// `[chromium, "firefox", ...]`, case insensitive
+fn browsers(value: &Expr) -> syn::Result<Vec<TokenStream2>> {
+    array(value)?
+        .elems
+        .iter()
+        .map(|browser| {
+            let name = match browser {
+                Expr::Path(path) if path.path.get_ident().is_some() => {
+                    path.path.get_ident().unwrap().to_string()
+                }
+                _ => string(browser)?.value(),
+            };
+            let variant = match name.to_ascii_lowercase().as_str() {
+                "chromium" => Ident::new("Chromium", browser.span()),
+                "firefox" => Ident::new("Firefox", browser.span()),
+                "webkit" => Ident::new("Webkit", browser.span()),
+                _ => {
+                    return Err(Error::new(
+                        browser.span(),
+                        "unknown browser, expected chromium, firefox or webkit",
+                    ))
+                }
+            };
+            Ok(quote!(::e2e::BrowserType::#variant))
+        })
+        .collect()
+}
+
+fn array(value: &Expr) -> syn::Result<&ExprArray> {
+    match value {
+        Expr::Array(array) => Ok(array),
+        _ => Err(Error::new(value.span(), "expected a list, e.g. `[...]`")),
+    }
+}
+
+fn string(value: &Expr) -> syn::Result<LitStr> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(string),
+            ..
+        }) => Ok(string.clone()),
+        _ => Err(Error::new(value.span(), "expected a string literal")),
+    }
+}
+
+/// `"500ms"`, `"30s"` or `"2m"`
+fn duration_millis(duration: &LitStr) -> syn::Result<u64> {
+    let value = duration.value();
+    let (number, unit_millis) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 1)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1_000)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60_000)
+    } else {
+        (value.as_str(), 0)
+    };
+    let millis = number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit_millis));
+    match millis {
+        Some(millis) if unit_millis > 0 => Ok(millis),
+        _ => Err(Error::new(
+            duration.span(),
+            "expected a duration such as \"500ms\", \"30s\" or \"2m\"",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{duration_millis, LitStr};
+    use proc_macro2::Span;
+
+    fn millis(duration: &str) -> Option<u64> {
+        duration_millis(&LitStr::new(duration, Span::call_site())).ok()
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(millis("500ms"), Some(500));
+        assert_eq!(millis("30s"), Some(30_000));
+        assert_eq!(millis("2m"), Some(120_000));
+        assert_eq!(millis(&format!("{}m", u64::MAX)), None);
+        assert_eq!(millis("30"), None);
+        assert_eq!(millis("1.5s"), None);
+        assert_eq!(millis("soon"), None);
+    }
+}
@@ -0,0 +1,58 @@
+//! Files saved while the tests run, e.g. screenshots of failing pages, kept in one directory per
+//! run so that successive runs don't overwrite each other.
+
+use crate::{device::Target, report::timestamp};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub(crate) struct Artifacts {
+    dir: PathBuf,
+}
+
+impl Artifacts {
+    /// Artifacts of a run starting now, in a new directory under `root`
+    pub fn new(root: &Path) -> io::Result<Self> {
+        // Colons are not allowed in Windows file names
+        let run_id = timestamp(SystemTime::now()).replace(':', "-");
+        Ok(Artifacts {
+            dir: std::env::current_dir()?.join(root).join(run_id),
+        })
+    }
+
+    /// Where the artifact `name` of an attempt of a test on a target goes, e.g.
+    /// `tests.hello_world-Chromium/attempt-1-screenshot.png`
+    pub fn path(&self, test_name: &str, target: Target, attempt: usize, name: &str) -> PathBuf {
+        self.test_dir(test_name, target)
+            .join(format!("attempt-{}-{}", attempt, name))
+    }
+
+    /// Directory of the artifacts of a test on a target, e.g. `tests.hello_world-Chromium`
+    /// or `tests.hello_world-Chromium-Pixel-like`
+    pub fn test_dir(&self, test_name: &str, target: Target) -> PathBuf {
+        let dir_name = match target.device {
+            Some(device) => format!("{}-{}-{}", test_name, target.browser_type, device),
+            None => format!("{}-{}", test_name, target.browser_type),
+        };
+        let dir_name: String = dir_name
+            .replace("::", ".")
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() => c,
+                '.' | '_' | '-' => c,
+                _ => '-',
+            })
+            .collect();
+        self.dir.join(dir_name)
+    }
+
+    /// Writes `contents` to `path`, creating its directory if needed
+    pub fn save(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+    }
+}
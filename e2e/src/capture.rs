@@ -0,0 +1,218 @@
+//! Captures everything a test outputs, so it can be shown with its result instead of being
+//! interleaved with the output of the tests running at the same time.
+//!
+//! Each attempt runs on a thread of its own with a single-threaded runtime, with the output of
+//! the thread captured. This covers `print!` and `eprint!`, panic messages, tasks spawned with
+//! `tokio::spawn` (they run on the same thread) and threads spawned with `std::thread::spawn`
+//! (a spawn hook passes the capture on to them). Records of the `log` and `tracing` crates are
+//! written the same way by the logger installed by the runner.
+//!
+//! Capturing the output of a thread needs nightly Rust: without the `nightly` feature, only the
+//! `log` and `tracing` records of the thread of the test are captured. The rest of its output
+//! goes straight to the terminal, in the middle of the progress of the run.
+
+use futures::future::{self, Either};
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug},
+    future::Future,
+    io::{self, Write as _},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+use tokio::{runtime, sync::oneshot};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Metadata, Subscriber,
+};
+
+/// Runs `test` on a new thread, capturing its output into `output`, until it completes or the
+/// returned future is dropped
+pub(crate) async fn run_on_test_thread<F, Fut>(
+    name: &str,
+    output: Arc<Mutex<Vec<u8>>>,
+    test: F,
+) -> io::Result<Fut::Output>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future,
+    Fut::Output: Send + 'static,
+{
+    let (result_sender, result) = oneshot::channel();
+    // Dropped with this future, which cancels the test and the tasks it spawned
+    let (_cancel, cancelled) = oneshot::channel::<()>();
+
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            capture_thread_output(output);
+            let runtime = runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("failed to build the runtime of a test");
+            let result = runtime.block_on(async move {
+                match future::select(Box::pin(test()), cancelled).await {
+                    Either::Left((result, _)) => Some(result),
+                    Either::Right(_) => None,
+                }
+            });
+            // Tasks left behind by the test must not keep its thread alive
+            runtime.shutdown_background();
+            if let Some(result) = result {
+                let _ = result_sender.send(result);
+            }
+        })?;
+
+    result
+        .await
+        .map_err(|_| io::Error::other("the thread of the test stopped without a result"))
+}
+
+thread_local! {
+    /// Where the loggers write the records of the current thread, if it runs a test
+    static TEST_OUTPUT: RefCell<Option<Arc<Mutex<Vec<u8>>>>> = const { RefCell::new(None) };
+}
+
+/// Captures what the current thread, and the threads it spawns, write to stdout and stderr
+#[cfg(feature = "nightly")]
+fn capture_thread_output(output: Arc<Mutex<Vec<u8>>>) {
+    TEST_OUTPUT.with(|test_output| *test_output.borrow_mut() = Some(output.clone()));
+    io::set_output_capture(Some(output));
+    // Hooks are inherited, so threads spawned by those threads are captured as well
+    thread::add_spawn_hook(|_| {
+        let output = io::set_output_capture(None);
+        io::set_output_capture(output.clone());
+        move || {
+            TEST_OUTPUT.with(|test_output| *test_output.borrow_mut() = output.clone());
+            io::set_output_capture(output);
+        }
+    });
+}
+
+/// Captures the records logged by the current thread
+#[cfg(not(feature = "nightly"))]
+fn capture_thread_output(output: Arc<Mutex<Vec<u8>>>) {
+    TEST_OUTPUT.with(|test_output| *test_output.borrow_mut() = Some(output));
+}
+
+/// Writes a record to the output of the test of the current thread, or to stderr
+fn write_record(record: fmt::Arguments<'_>) {
+    let written = TEST_OUTPUT.with(|test_output| match &*test_output.borrow() {
+        Some(output) => {
+            let _ = writeln!(output.lock().unwrap(), "{}", record);
+            true
+        }
+        None => false,
+    });
+    if !written {
+        eprintln!("{}", record);
+    }
+}
+
+/// Sends the records of `log` and events of `tracing` up to `level` to the output of the test
+/// that emitted them. Called before any test runs, so a global logger or subscriber can only be
+/// kept by setting it before [`e2e_main`](crate::e2e_main), e.g. in the `main` of a
+/// `harness = false` test, in which case this does nothing for its crate.
+pub(crate) fn install_loggers(level: log::LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+    let _ = tracing::subscriber::set_global_default(CaptureSubscriber {
+        max_level: match level {
+            log::LevelFilter::Off => LevelFilter::OFF,
+            log::LevelFilter::Error => LevelFilter::ERROR,
+            log::LevelFilter::Warn => LevelFilter::WARN,
+            log::LevelFilter::Info => LevelFilter::INFO,
+            log::LevelFilter::Debug => LevelFilter::DEBUG,
+            log::LevelFilter::Trace => LevelFilter::TRACE,
+        },
+        next_span_id: AtomicU64::new(1),
+    });
+}
+
+static LOGGER: CaptureLogger = CaptureLogger;
+
+/// Writes records to the output of the test that logged them
+struct CaptureLogger;
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            write_record(format_args!(
+                "{:>5} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Writes events like [`CaptureLogger`], spans are not recorded
+struct CaptureSubscriber {
+    max_level: LevelFilter,
+    next_span_id: AtomicU64,
+}
+
+impl Subscriber for CaptureSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.max_level >= *metadata.level()
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level)
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        write_record(format_args!(
+            "{:>5} {}: {}{}",
+            metadata.level(),
+            metadata.target(),
+            fields.message,
+            fields.others
+        ));
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    others: String,
+}
+
+impl Visit for EventFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        use fmt::Write;
+
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.others, " {}={:?}", field.name(), value);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! What the page of a test logs to its console and the errors it doesn't catch.
+//!
+//! The playwright crate doesn't forward `pageerror` events, so an init script reports uncaught
+//! errors and unhandled rejections through `console.error` with a marker the collector recognizes.
+
+use futures::StreamExt;
+use playwright::api::{
+    page::{Event, Page},
+    BrowserContext,
+};
+use std::{
+    fmt::{Display, Formatter},
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time};
+
+const PAGE_ERROR_MARKER: &str = "[e2e:pageerror] ";
+
+const PAGE_ERROR_SCRIPT: &str = r#"
+window.addEventListener('error', event => {
+    const error = event.error;
+    console.error('[e2e:pageerror] ' + (error && error.stack ? error.stack : event.message));
+});
+window.addEventListener('unhandledrejection', event => {
+    const reason = event.reason;
+    console.error('[e2e:pageerror] Unhandled promise rejection: '
+        + (reason && reason.stack ? reason.stack : reason));
+});
+"#;
+
+pub enum PageMessage {
+    /// A call to the console API, e.g. `console.error`
+    Console {
+        /// `log`, `error`, `warning`, ...
+        kind: String,
+        text: String,
+        /// `url:line:column` of the call
+        location: Option<String>,
+    },
+    /// An exception the page didn't catch, with its stack when available
+    Error(String),
+}
+
+impl Display for PageMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageMessage::Console {
+                kind,
+                text,
+                location: Some(location),
+            } => write!(f, "[{}] {} ({})", kind, text, location),
+            PageMessage::Console { kind, text, .. } => write!(f, "[{}] {}", kind, text),
+            PageMessage::Error(error) => write!(f, "[uncaught] {}", error),
+        }
+    }
+}
+
+/// Collects the messages of a page in the background until it is closed
+pub(crate) struct ConsoleCollector {
+    messages: Arc<Mutex<Vec<PageMessage>>>,
+    task: JoinHandle<()>,
+}
+
+impl ConsoleCollector {
+    /// Reports uncaught errors of the pages of `context`, must be called before they are opened
+    pub async fn install(context: &BrowserContext) -> Result<(), Arc<playwright::Error>> {
+        context.add_init_script(PAGE_ERROR_SCRIPT).await
+    }
+
+    pub fn start(page: &Page) -> Result<Self, playwright::Error> {
+        let mut events = page.subscribe_event()?;
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let task = tokio::spawn({
+            let messages = messages.clone();
+            async move {
+                while let Some(event) = events.next().await {
+                    let message = match event {
+                        Ok(Event::Console(message)) => message,
+                        // Every message sent before was received
+                        Ok(Event::Close) => break,
+                        // Other events, or lagging behind and the missed messages are lost
+                        _ => continue,
+                    };
+                    let (kind, text) = match (message.r#type(), message.text()) {
+                        (Ok(kind), Ok(text)) => (kind, text),
+                        _ => continue,
+                    };
+                    let message = match text.strip_prefix(PAGE_ERROR_MARKER) {
+                        Some(error) => PageMessage::Error(error.to_string()),
+                        None => PageMessage::Console {
+                            kind,
+                            text,
+                            location: message
+                                .location()
+                                .ok()
+                                .filter(|location| !location.url.is_empty())
+                                .map(|location| {
+                                    format!(
+                                        "{}:{}:{}",
+                                        location.url,
+                                        location.line_number + 1,
+                                        location.column_number + 1
+                                    )
+                                }),
+                        },
+                    };
+                    messages.lock().unwrap().push(message);
+                }
+            }
+        });
+        Ok(ConsoleCollector { messages, task })
+    }
+
+    /// Waits for the page to be closed and returns its messages, or the messages received so far
+    /// if its closing isn't reported in time
+    pub async fn finish(mut self) -> Vec<PageMessage> {
+        if time::timeout(Duration::from_secs(1), &mut self.task)
+            .await
+            .is_err()
+        {
+            self.task.abort();
+        }
+        mem::take(&mut *self.messages.lock().unwrap())
+    }
+}
@@ -0,0 +1,267 @@
+//! Options of the browser context each attempt runs in, to test localized and responsive
+//! layouts: viewport, locale, timezone, geolocation, ...
+//!
+//! They are set for the whole run with command-line flags, see
+//! [`Options::context`](crate::options::Options::context), and per test with
+//! [`TestMeta::context`](crate::meta::TestMeta::context):
+//!
+//! ```ignore
+//! #[e2e::test(locale = "fr-FR", timezone = "Europe/Paris", viewport = "375x812")]
+//! async fn mobile_checkout(ctx: Context) -> Result<()> {
+//!     ...
+//! }
+//! ```
+//!
+//! The options a test sets replace the ones of the run, except its headers and permissions which
+//! are added to them.
+
+use playwright::api::browser::ContextBuilder;
+pub use playwright::api::ColorScheme;
+use std::{collections::HashMap, str::FromStr};
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ContextOptions {
+    /// Size of the pages, 1280x720 unless set
+    pub viewport: Option<Viewport>,
+    /// e.g. `fr-FR`, sets `navigator.language`, the `Accept-Language` header and how numbers
+    /// and dates are formatted
+    pub locale: Option<&'static str>,
+    /// e.g. `Europe/Paris`, the one of the machine unless set
+    pub timezone: Option<&'static str>,
+    /// Position reported by the geolocation API, which also needs the `geolocation` permission
+    pub geolocation: Option<Geolocation>,
+    /// Value of the `prefers-color-scheme` media feature
+    pub color_scheme: Option<ColorScheme>,
+    pub user_agent: Option<&'static str>,
+    /// Granted to the pages, e.g. `geolocation` or `clipboard-read`
+    pub permissions: &'static [&'static str],
+    /// Sent with every request of the pages
+    pub extra_http_headers: &'static [(&'static str, &'static str)],
+    /// Whether the pages run JavaScript, they do unless set
+    pub javascript_enabled: Option<bool>,
+}
+
+impl ContextOptions {
+    pub const fn new() -> Self {
+        ContextOptions {
+            viewport: None,
+            locale: None,
+            timezone: None,
+            geolocation: None,
+            color_scheme: None,
+            user_agent: None,
+            permissions: &[],
+            extra_http_headers: &[],
+            javascript_enabled: None,
+        }
+    }
+
+    pub const fn viewport(mut self, width: u32, height: u32) -> Self {
+        self.viewport = Some(Viewport { width, height });
+        self
+    }
+
+    pub const fn locale(mut self, locale: &'static str) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    pub const fn timezone(mut self, timezone: &'static str) -> Self {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    pub const fn geolocation(mut self, geolocation: Geolocation) -> Self {
+        self.geolocation = Some(geolocation);
+        self
+    }
+
+    pub const fn color_scheme(mut self, color_scheme: ColorScheme) -> Self {
+        self.color_scheme = Some(color_scheme);
+        self
+    }
+
+    pub const fn user_agent(mut self, user_agent: &'static str) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
+
+    pub const fn permissions(mut self, permissions: &'static [&'static str]) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub const fn extra_http_headers(
+        mut self,
+        headers: &'static [(&'static str, &'static str)],
+    ) -> Self {
+        self.extra_http_headers = headers;
+        self
+    }
+
+    pub const fn javascript_enabled(mut self, enabled: bool) -> Self {
+        self.javascript_enabled = Some(enabled);
+        self
+    }
+
+    /// These options over the ones of the run, see the [module documentation](self)
+    pub(crate) fn over(&self, run: &ContextOptions) -> MergedContextOptions {
+        let mut permissions = Vec::new();
+        for &permission in run.permissions.iter().chain(self.permissions) {
+            if !permissions.iter().any(|granted| granted == permission) {
+                permissions.push(permission.to_string());
+            }
+        }
+        MergedContextOptions {
+            options: ContextOptions {
+                viewport: self.viewport.or(run.viewport),
+                locale: self.locale.or(run.locale),
+                timezone: self.timezone.or(run.timezone),
+                geolocation: self.geolocation.or(run.geolocation),
+                color_scheme: self.color_scheme.or(run.color_scheme),
+                user_agent: self.user_agent.or(run.user_agent),
+                permissions: &[],
+                extra_http_headers: &[],
+                javascript_enabled: self.javascript_enabled.or(run.javascript_enabled),
+            },
+            permissions,
+            extra_http_headers: run
+                .extra_http_headers
+                .iter()
+                .chain(self.extra_http_headers)
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// The options of a test over the ones of the run, ready to build its context with
+pub(crate) struct MergedContextOptions {
+    options: ContextOptions,
+    permissions: Vec<String>,
+    pub extra_http_headers: HashMap<String, String>,
+}
+
+impl MergedContextOptions {
+    /// Sets the options on `builder`, but the headers which are set with the ones of the runner
+    pub fn configure<'e, 'f, 'g, 'h, 'i, 'j, 'k>(
+        &'h self,
+        mut builder: ContextBuilder<'e, 'f, 'g, 'h, 'i, 'j, 'k>,
+    ) -> ContextBuilder<'e, 'f, 'g, 'h, 'i, 'j, 'k> {
+        let options = &self.options;
+        if let Some(viewport) = options.viewport {
+            builder = builder.viewport(Some(viewport.into()));
+        }
+        if let Some(locale) = options.locale {
+            builder = builder.locale(locale);
+        }
+        if let Some(timezone) = options.timezone {
+            builder = builder.timezone_id(timezone);
+        }
+        if let Some(geolocation) = options.geolocation {
+            builder = builder.geolocation(geolocation.into());
+        }
+        if let Some(color_scheme) = options.color_scheme {
+            builder = builder.color_scheme(color_scheme);
+        }
+        if let Some(user_agent) = options.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if !self.permissions.is_empty() {
+            builder = builder.permissions(&self.permissions);
+        }
+        if let Some(javascript_enabled) = options.javascript_enabled {
+            builder = builder.js_enabled(javascript_enabled);
+        }
+        builder
+    }
+}
+
+/// Size of the pages in CSS pixels, `1280x720` in flags
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Viewport {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s.trim().split_once('x').ok_or(())?;
+        Ok(Viewport {
+            width: width.trim().parse().map_err(drop)?,
+            height: height.trim().parse().map_err(drop)?,
+        })
+    }
+}
+
+impl From<Viewport> for playwright::api::Viewport {
+    fn from(viewport: Viewport) -> Self {
+        playwright::api::Viewport {
+            width: viewport.width as i32,
+            height: viewport.height as i32,
+        }
+    }
+}
+
+/// `48.8566,2.3522` in flags, optionally followed by the accuracy in meters
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Geolocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub accuracy: Option<f64>,
+}
+
+impl Geolocation {
+    pub const fn new(latitude: f64, longitude: f64) -> Self {
+        Geolocation {
+            latitude,
+            longitude,
+            accuracy: None,
+        }
+    }
+
+    pub const fn accuracy(mut self, meters: f64) -> Self {
+        self.accuracy = Some(meters);
+        self
+    }
+}
+
+impl FromStr for Geolocation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<f64>().map_err(drop))
+            .collect::<Result<Vec<_>, _>>()?;
+        match parts[..] {
+            [latitude, longitude] => Ok(Geolocation::new(latitude, longitude)),
+            [latitude, longitude, accuracy] => {
+                Ok(Geolocation::new(latitude, longitude).accuracy(accuracy))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<Geolocation> for playwright::api::Geolocation {
+    fn from(geolocation: Geolocation) -> Self {
+        playwright::api::Geolocation {
+            latitude: geolocation.latitude,
+            longitude: geolocation.longitude,
+            accuracy: geolocation.accuracy,
+        }
+    }
+}
+
+/// `light`, `dark` or `no-preference`
+pub(crate) fn parse_color_scheme(value: &str) -> Option<ColorScheme> {
+    match value.trim() {
+        "light" => Some(ColorScheme::Light),
+        "dark" => Some(ColorScheme::Dark),
+        "no-preference" => Some(ColorScheme::NoPreference),
+        _ => None,
+    }
+}
@@ -0,0 +1,138 @@
+//! Device profiles the browser contexts emulate, a dimension of the test matrix next to the
+//! browsers: with `--device pixel,iphone`, each test runs in each browser as each device, e.g.
+//! as "Chromium / Pixel-like" and "Webkit / iPhone-like".
+//!
+//! Firefox can't emulate mobile devices, tests don't run as them there and the runner reports it.
+//! The run fails when a browser can't emulate any of its devices. The
+//! [context options](crate::context_options) of the run and of the tests apply over the profile,
+//! e.g. a test setting its viewport keeps it on every device.
+
+use crate::{context_options::Viewport, BrowserType};
+use playwright::api::browser::ContextBuilder;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Device {
+    /// Shown next to the browser in reports
+    pub name: &'static str,
+    pub viewport: Viewport,
+    /// Device pixels per CSS pixel
+    pub device_scale_factor: f64,
+    /// Whether the `meta viewport` tag is taken into account
+    pub is_mobile: bool,
+    pub has_touch: bool,
+    /// The one of the browser unless set
+    pub user_agent: Option<&'static str>,
+}
+
+impl Device {
+    pub const DESKTOP: Device = Device {
+        name: "Desktop",
+        viewport: Viewport {
+            width: 1280,
+            height: 720,
+        },
+        device_scale_factor: 1.0,
+        is_mobile: false,
+        has_touch: false,
+        user_agent: None,
+    };
+
+    pub const PIXEL: Device = Device {
+        name: "Pixel-like",
+        viewport: Viewport {
+            width: 393,
+            height: 851,
+        },
+        device_scale_factor: 2.75,
+        is_mobile: true,
+        has_touch: true,
+        user_agent: Some(
+            "Mozilla/5.0 (Linux; Android 11; Pixel 5) AppleWebKit/537.36 (KHTML, like Gecko) \
+             Chrome/90.0.4430.91 Mobile Safari/537.36",
+        ),
+    };
+
+    pub const IPHONE: Device = Device {
+        name: "iPhone-like",
+        viewport: Viewport {
+            width: 390,
+            height: 844,
+        },
+        device_scale_factor: 3.0,
+        is_mobile: true,
+        has_touch: true,
+        user_agent: Some(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 14_4 like Mac OS X) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/14.0.3 Mobile/15E148 Safari/604.1",
+        ),
+    };
+
+    pub const IPAD: Device = Device {
+        name: "iPad-like",
+        viewport: Viewport {
+            width: 810,
+            height: 1080,
+        },
+        device_scale_factor: 2.0,
+        is_mobile: true,
+        has_touch: true,
+        user_agent: Some(
+            "Mozilla/5.0 (iPad; CPU OS 14_4 like Mac OS X) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/14.0.3 Mobile/15E148 Safari/604.1",
+        ),
+    };
+
+    /// The profiles `--device` accepts, by their name without `-like`, case insensitive
+    pub const ALL: [Device; 4] = [Device::DESKTOP, Device::PIXEL, Device::IPHONE, Device::IPAD];
+
+    pub fn find(name: &str) -> Option<Device> {
+        let name = name.trim().to_ascii_lowercase();
+        Device::ALL.iter().copied().find(|device| {
+            let device_name = device.name.to_ascii_lowercase();
+            device_name == name || device_name.trim_end_matches("-like") == name
+        })
+    }
+
+    /// Whether `browser_type` can emulate the device
+    pub fn runs_in(&self, browser_type: BrowserType) -> bool {
+        !(self.is_mobile && browser_type == BrowserType::Firefox)
+    }
+
+    pub(crate) fn configure<'e, 'f, 'g, 'h, 'i, 'j, 'k>(
+        &self,
+        mut builder: ContextBuilder<'e, 'f, 'g, 'h, 'i, 'j, 'k>,
+    ) -> ContextBuilder<'e, 'f, 'g, 'h, 'i, 'j, 'k> {
+        builder = builder
+            .viewport(Some(self.viewport.into()))
+            .device_scale_factor(self.device_scale_factor)
+            .is_mobile(self.is_mobile)
+            .has_touch(self.has_touch);
+        if let Some(user_agent) = self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        builder
+    }
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// What a test runs on: a browser, emulating a device if the run has some
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Target {
+    pub browser_type: BrowserType,
+    pub device: Option<Device>,
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.device {
+            Some(device) => write!(f, "{} / {}", self.browser_type, device),
+            None => write!(f, "{}", self.browser_type),
+        }
+    }
+}
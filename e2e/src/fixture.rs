@@ -0,0 +1,371 @@
+//! Resources tests need, built by the runner before a test and torn down after it: a logged-in
+//! page, a seeded database, an API client, a temporary directory, ...
+//!
+//! ```ignore
+//! struct Database(Pool);
+//!
+//! #[async_trait]
+//! impl Fixture for Database {
+//!     // Shared by every test of the run
+//!     const SCOPE: Scope = Scope::Run;
+//!
+//!     async fn setup(_ctx: &FixtureContext<'_>) -> Result<Self> {
+//!         Ok(Database(seed().await?))
+//!     }
+//!
+//!     async fn teardown(&self) -> Result<()> {
+//!         self.0.close().await
+//!     }
+//! }
+//!
+//! #[e2e::test(fixtures = [Database])]
+//! async fn list_users(ctx: Context) -> Result<()> {
+//!     let database = ctx.fixture::<Database>()?;
+//!     ...
+//! }
+//! ```
+//!
+//! A test declares the fixtures it uses, which are set up in that order before it starts, as
+//! part of its timeout. A fixture can use other fixtures of the same or a wider scope through
+//! [`FixtureContext::fixture`], they are set up as needed.
+
+use crate::{catch_panic, timeout::bounded, BrowserType};
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use playwright::api::{Browser, Page};
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    error::Error,
+    fmt::{Debug, Display, Formatter},
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::OnceCell;
+
+/// How long a fixture lives, from the narrowest to the widest
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    /// Set up for each attempt of a test, with its page
+    Test,
+    /// Shared by the tests running in the same browser
+    Browser,
+    /// Shared by every test of the run
+    Run,
+}
+
+#[async_trait]
+pub trait Fixture: Send + Sync + Sized + 'static {
+    const SCOPE: Scope = Scope::Test;
+
+    async fn setup(ctx: &FixtureContext<'_>) -> Result<Self>;
+
+    /// Called once the tests using the fixture are done, in the reverse order of the setups.
+    /// Test fixtures are torn down before the page is closed. Each teardown may take as long as
+    /// the timeout of the test, or the default one for fixtures of wider scopes.
+    async fn teardown(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A fixture declared by a test, see [`TestMeta::fixtures`](crate::meta::TestMeta::fixtures)
+#[derive(Copy, Clone)]
+pub struct FixtureRef {
+    name: fn() -> &'static str,
+    setup: for<'a> fn(&'a FixtureContext<'a>) -> BoxFuture<'a, Result<()>>,
+}
+
+impl FixtureRef {
+    pub const fn of<T: Fixture>() -> Self {
+        FixtureRef {
+            name: type_name::<T>,
+            setup: setup::<T>,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        (self.name)()
+    }
+
+    pub(crate) fn setup<'a>(&self, ctx: &'a FixtureContext<'a>) -> BoxFuture<'a, Result<()>> {
+        (self.setup)(ctx)
+    }
+}
+
+fn setup<'a, T: Fixture>(ctx: &'a FixtureContext<'a>) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move { ctx.fixture::<T>().await.map(drop) })
+}
+
+impl Debug for FixtureRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// What a fixture is set up with, or a [hook](crate::hook) run with, depending on its scope
+#[derive(Copy, Clone)]
+pub struct FixtureContext<'a> {
+    scope: Scope,
+    base_url: &'a str,
+    browser: Option<(BrowserType, &'a Browser)>,
+    page: Option<&'a Page>,
+    fixtures: &'a Fixtures,
+}
+
+impl<'a> FixtureContext<'a> {
+    /// Context of the test fixtures of an attempt
+    pub(crate) fn new(
+        base_url: &'a str,
+        browser_type: BrowserType,
+        browser: &'a Browser,
+        page: &'a Page,
+        fixtures: &'a Fixtures,
+    ) -> Self {
+        FixtureContext {
+            scope: Scope::Test,
+            base_url,
+            browser: Some((browser_type, browser)),
+            page: Some(page),
+            fixtures,
+        }
+    }
+
+    /// Context of the `before_all` and `after_all` hooks, of `browser` if they run in each one
+    pub(crate) fn shared(
+        base_url: &'a str,
+        browser: Option<(BrowserType, &'a Browser)>,
+        fixtures: &'a Fixtures,
+    ) -> Self {
+        FixtureContext {
+            scope: match browser {
+                Some(_) => Scope::Browser,
+                None => Scope::Run,
+            },
+            base_url,
+            browser,
+            page: None,
+            fixtures,
+        }
+    }
+
+    /// Scope of the fixture being set up
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    /// URL of the server under test, without trailing slash
+    pub fn base_url(&self) -> &str {
+        self.base_url
+    }
+
+    /// Absolute URL of `path` on the server under test
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// Browser of the tests, unless the fixture is shared by the whole run
+    pub fn browser_type(&self) -> Option<BrowserType> {
+        self.browser.map(|(browser_type, _)| browser_type)
+    }
+
+    /// Browser of the tests, unless the fixture is shared by the whole run, e.g. to open a second
+    /// context
+    pub fn browser(&self) -> Option<&'a Browser> {
+        self.browser.map(|(_, browser)| browser)
+    }
+
+    /// Page of the test, for test fixtures only
+    pub fn page(&self) -> Option<&'a Page> {
+        self.page
+    }
+
+    /// Another fixture, set up if it wasn't yet. Its scope can't be narrower than the one of
+    /// the fixture being set up.
+    pub async fn fixture<T: Fixture>(&self) -> Result<Arc<T>> {
+        if T::SCOPE < self.scope {
+            return Err(NarrowerScope {
+                fixture: type_name::<T>(),
+                scope: T::SCOPE,
+                from: self.scope,
+            }
+            .into());
+        }
+        let ctx = FixtureContext {
+            scope: T::SCOPE,
+            browser: self.browser.filter(|_| T::SCOPE <= Scope::Browser),
+            page: self.page.filter(|_| T::SCOPE == Scope::Test),
+            ..*self
+        };
+        self.fixtures.store(T::SCOPE).get_or_setup::<T>(&ctx).await
+    }
+}
+
+/// The fixtures an attempt can use, by scope
+#[derive(Clone)]
+pub(crate) struct Fixtures {
+    pub run: Arc<FixtureStore>,
+    pub browser: Arc<FixtureStore>,
+    pub test: Arc<FixtureStore>,
+}
+
+impl Fixtures {
+    fn store(&self, scope: Scope) -> &FixtureStore {
+        match scope {
+            Scope::Test => &self.test,
+            Scope::Browser => &self.browser,
+            Scope::Run => &self.run,
+        }
+    }
+
+    /// The fixture if it was set up
+    pub fn get<T: Fixture>(&self) -> Option<Arc<T>> {
+        self.store(T::SCOPE).get::<T>()
+    }
+}
+
+type Slot = Arc<OnceCell<Arc<dyn Any + Send + Sync>>>;
+type Teardown = Box<dyn FnOnce() -> BoxFuture<'static, Result<()>> + Send>;
+
+/// Fixtures of one scope, e.g. of one browser, set up at most once each
+#[derive(Default)]
+pub(crate) struct FixtureStore {
+    fixtures: Mutex<HashMap<TypeId, Slot>>,
+    /// In the order of the setups
+    teardowns: Mutex<Vec<(&'static str, Teardown)>>,
+}
+
+impl FixtureStore {
+    async fn get_or_setup<T: Fixture>(&self, ctx: &FixtureContext<'_>) -> Result<Arc<T>> {
+        let cell = self
+            .fixtures
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .clone();
+        // Fixtures shared by tests running at the same time are only set up once
+        let fixture = cell
+            .get_or_try_init(|| async {
+                let fixture = Arc::new(
+                    catch_panic(T::setup(ctx))
+                        .await
+                        .with_context(|| FixtureFailed::setup(type_name::<T>()))?,
+                );
+                let teardown: Teardown = Box::new({
+                    let fixture = fixture.clone();
+                    move || Box::pin(async move { fixture.teardown().await })
+                });
+                self.teardowns
+                    .lock()
+                    .unwrap()
+                    .push((type_name::<T>(), teardown));
+                Ok::<_, anyhow::Error>(fixture as Arc<dyn Any + Send + Sync>)
+            })
+            .await?;
+        Ok(downcast(fixture))
+    }
+
+    fn get<T: Fixture>(&self) -> Option<Arc<T>> {
+        let fixtures = self.fixtures.lock().unwrap();
+        fixtures.get(&TypeId::of::<T>())?.get().map(downcast)
+    }
+
+    /// Tears the fixtures down, in the reverse order of their setups and each within `timeout`,
+    /// adding the failures to `errors`
+    pub async fn teardown(&self, timeout: Duration, errors: &mut Vec<anyhow::Error>) {
+        let teardowns = mem::take(&mut *self.teardowns.lock().unwrap());
+        self.fixtures.lock().unwrap().clear();
+        for (fixture, teardown) in teardowns.into_iter().rev() {
+            if let Err(err) = bounded(timeout, catch_panic(teardown())).await {
+                errors.push(err.context(FixtureFailed::teardown(fixture)));
+            }
+        }
+    }
+}
+
+fn downcast<T: Fixture>(fixture: &Arc<dyn Any + Send + Sync>) -> Arc<T> {
+    fixture
+        .clone()
+        .downcast()
+        .unwrap_or_else(|_| unreachable!("fixtures are stored by type"))
+}
+
+struct FixtureFailed {
+    action: &'static str,
+    fixture: &'static str,
+}
+
+impl FixtureFailed {
+    fn setup(fixture: &'static str) -> Self {
+        FixtureFailed {
+            action: "set up",
+            fixture,
+        }
+    }
+
+    fn teardown(fixture: &'static str) -> Self {
+        FixtureFailed {
+            action: "tear down",
+            fixture,
+        }
+    }
+}
+
+impl Display for FixtureFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to {} fixture {}", self.action, self.fixture)
+    }
+}
+
+impl Debug for FixtureFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+struct NarrowerScope {
+    fixture: &'static str,
+    scope: Scope,
+    from: Scope,
+}
+
+impl Display for NarrowerScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Fixture {} has the {:?} scope, it can't be used by a fixture of the {:?} scope",
+            self.fixture, self.scope, self.from
+        )
+    }
+}
+
+impl Debug for NarrowerScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for NarrowerScope {}
+
+/// A test used a fixture without declaring it
+pub(crate) struct FixtureNotDeclared(pub &'static str);
+
+impl Display for FixtureNotDeclared {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Fixture {} is not declared by the test, add it to its `fixtures`",
+            self.0
+        )
+    }
+}
+
+impl Debug for FixtureNotDeclared {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for FixtureNotDeclared {}
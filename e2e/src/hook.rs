@@ -0,0 +1,192 @@
+//! Code run around the tests rather than as one: seeding data before the run, logging in before
+//! each test, cleaning up after them, ...
+//!
+//! ```ignore
+//! #[e2e::before_all]
+//! async fn seed(ctx: &FixtureContext<'_>) -> Result<()> {
+//!     ...
+//! }
+//!
+//! #[e2e::before_each]
+//! async fn accept_cookies(ctx: &FixtureContext<'_>) -> Result<()> {
+//!     let page = ctx.page().unwrap();
+//!     ...
+//! }
+//! ```
+//!
+//! - `before_all` hooks run once before the tests, and `before_all(per_browser)` ones once each
+//!   browser is launched. When one fails, the tests depending on it fail without running.
+//! - `after_all` and `after_all(per_browser)` hooks run once the tests are done, even if a hook
+//!   before them failed.
+//! - `before_each` hooks run before each attempt of a test, after its fixtures are set up, as
+//!   part of its timeout.
+//! - `after_each` hooks run after each attempt, before its fixtures are torn down. When one fails,
+//!   it fails the test if it passed.
+//! - `after_*` hooks may each take as long as the timeout of the test, or the default one for
+//!   `after_all` hooks.
+//!
+//! Hooks of a kind run in the order of their names. They can use [fixtures](crate::fixture) of
+//! their scope, or wider, through [`FixtureContext::fixture`].
+
+use crate::{
+    catch_panic,
+    fixture::{FixtureContext, Scope},
+    labelled, registry,
+    timeout::bounded,
+};
+use anyhow::{Context as _, Result};
+use futures::future::BoxFuture;
+use std::{
+    fmt::{Debug, Display, Formatter},
+    time::Duration,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum When {
+    BeforeAll,
+    AfterAll,
+    BeforeEach,
+    AfterEach,
+}
+
+impl Display for When {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            When::BeforeAll => "before_all",
+            When::AfterAll => "after_all",
+            When::BeforeEach => "before_each",
+            When::AfterEach => "after_each",
+        })
+    }
+}
+
+pub type HookFn = for<'a> fn(&'a FixtureContext<'a>) -> BoxFuture<'a, Result<()>>;
+
+/// A hook, usually declared with one of the hook attributes, see the
+/// [module documentation](self)
+pub struct Hook {
+    when: When,
+    scope: Scope,
+    name: &'static str,
+    run: HookFn,
+}
+
+impl Hook {
+    /// `before_all` and `after_all` hooks run once for the run, unless
+    /// [`per_browser`](Self::per_browser)
+    pub const fn new(when: When, name: &'static str, run: HookFn) -> Self {
+        let scope = match when {
+            When::BeforeAll | When::AfterAll => Scope::Run,
+            When::BeforeEach | When::AfterEach => Scope::Test,
+        };
+        Hook {
+            when,
+            scope,
+            name,
+            run,
+        }
+    }
+
+    /// Runs a `before_all` or `after_all` hook in each browser
+    pub const fn per_browser(mut self) -> Self {
+        self.scope = Scope::Browser;
+        self
+    }
+
+    pub fn when(&self) -> When {
+        self.when
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Debug for Hook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} hook {}", self.when, self.name)
+    }
+}
+
+/// The hooks of a run
+pub(crate) struct Hooks(Vec<&'static Hook>);
+
+impl Hooks {
+    pub fn registered() -> Self {
+        Hooks(registry::hooks())
+    }
+
+    fn of(&self, when: When, scope: Scope) -> impl Iterator<Item = &'static Hook> + '_ {
+        self.0
+            .iter()
+            .copied()
+            .filter(move |hook| hook.when == when && hook.scope == scope)
+    }
+
+    /// Runs the hooks until one fails, returning it with its error
+    pub async fn run_before(
+        &self,
+        when: When,
+        ctx: &FixtureContext<'_>,
+    ) -> Result<(), (&'static Hook, anyhow::Error)> {
+        for hook in self.of(when, ctx.scope()) {
+            let label = format!("{:?}", hook);
+            if let Err(err) = labelled(label, catch_panic((hook.run)(ctx))).await {
+                return Err((hook, err.context(HookFailed(hook))));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs every hook, each within `timeout`, even when some fail, adding their failures to
+    /// `errors`
+    pub async fn run_after(
+        &self,
+        when: When,
+        ctx: &FixtureContext<'_>,
+        timeout: Duration,
+        errors: &mut Vec<anyhow::Error>,
+    ) {
+        for hook in self.of(when, ctx.scope()) {
+            let result = bounded(timeout, catch_panic((hook.run)(ctx))).await;
+            if let Err(err) = result.with_context(|| HookFailed(hook)) {
+                errors.push(err);
+            }
+        }
+    }
+}
+
+struct HookFailed(&'static Hook);
+
+impl Display for HookFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The {:?} failed", self.0)
+    }
+}
+
+impl Debug for HookFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// A test was not run as a hook it depends on failed
+pub(crate) struct NotRun(pub &'static Hook);
+
+impl Display for NotRun {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not run, the {:?} failed", self.0)
+    }
+}
+
+impl Debug for NotRun {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for NotRun {}
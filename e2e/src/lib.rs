@@ -1,136 +1,326 @@
-#![feature(custom_test_frameworks)]
-#![feature(internal_output_capture)]
-#![test_runner(e2e_test_runner)]
-
-#[cfg(test)]
-mod tests;
+#![cfg_attr(feature = "nightly", feature(internal_output_capture))]
+#![cfg_attr(feature = "nightly", feature(thread_spawn_hook))]
 
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::{stream, FutureExt, StreamExt};
-use pin_project::pin_project;
-use playwright::{api::Page, Playwright};
+use futures::{
+    future::{self, Either},
+    stream, FutureExt, StreamExt,
+};
+use playwright::{
+    api::{browser::RecordVideo, Browser, Page, Response},
+    Playwright,
+};
 use std::{
     any::{type_name, Any},
     collections::HashMap,
     error::Error,
     fmt::{Debug, Display, Formatter},
+    fs,
     future::Future,
-    panic::{catch_unwind, AssertUnwindSafe},
-    pin::Pin,
-    process::{Command, Stdio},
+    io::Write as _,
+    mem,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    str::FromStr,
     sync::{Arc, Mutex},
-    task::Poll,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{runtime::Runtime, sync::Semaphore};
+
+use crate::{
+    artifacts::Artifacts,
+    console::{ConsoleCollector, PageMessage},
+    device::{Device, Target},
+    fixture::{Fixture, FixtureContext, FixtureNotDeclared, FixtureStore, Fixtures},
+    hook::{Hook, Hooks, NotRun, When},
+    meta::TestMeta,
+    options::{Format, Options, OptionsError, Video},
+    report::{json::JsonReporter, pretty::PrettyReporter, FailedToWriteReport, Reporter, Summary},
+    result::{Attempt, TestOutcome, TestResult, TestStatus},
+    server::{Server, ServerLog, TEST_ID_HEADER},
+    timeout::{labelled, Deadline, PendingAwaits, TimedOut},
 };
-use tokio::runtime::Runtime;
 
+mod artifacts;
+mod capture;
+mod console;
+mod report;
+mod result;
+
+pub mod context_options;
+pub mod device;
+pub mod fixture;
+pub mod hook;
+pub mod meta;
+pub mod options;
 pub mod playwright_ext;
+pub mod registry;
+pub mod server;
+pub mod timeout;
 
-pub fn e2e_test_runner(tests: &[&dyn Testable]) {
-    let mut web_server = Command::new("target/debug/web")
-        .current_dir("..")
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .unwrap();
+pub use e2e_macros::{after_all, after_each, before_all, before_each, test};
 
-    let runtime = Runtime::new().unwrap();
-    let results = runtime.block_on(run_tests(tests));
+/// Runs the tests declared with [`#[e2e::test]`](test), from the `main` of a test with
+/// `harness = false`, which works on stable Rust:
+///
+/// ```ignore
+/// // tests/e2e.rs, with `[[test]] name = "e2e"` and `harness = false` in Cargo.toml
+/// #[e2e::test]
+/// async fn hello_world(ctx: Context) -> Result<()> {
+///     ...
+/// }
+///
+/// fn main() {
+///     e2e::e2e_main()
+/// }
+/// ```
+pub fn e2e_main() {
+    e2e_main_with(Server::default())
+}
 
-    let exit_code = match results {
-        Ok(test_results) => {
-            println!("\nSummary:");
+/// [`e2e_main`] against `server`, see [`e2e_test_runner_with`]
+pub fn e2e_main_with(server: Server) {
+    e2e_test_runner_with(&[], server)
+}
+
+pub fn e2e_test_runner(tests: &[&'static dyn Testable]) {
+    e2e_test_runner_with(tests, Server::default())
+}
+
+/// Runs the tests, and the ones declared with [`#[e2e::test]`](test), against `server`, unless
+/// it is overridden by `--server-url` or `--server-config`
+///
+/// Use it to define your own test runner when the default `web` binary doesn't fit:
+///
+/// ```ignore
+/// #![test_runner(my_test_runner)]
+///
+/// fn my_test_runner(tests: &[&'static dyn e2e::Testable]) {
+///     e2e::e2e_test_runner_with(tests, Server::Launch(ServerLauncher::new("./my-server")))
+/// }
+/// ```
+pub fn e2e_test_runner_with(tests: &[&'static dyn Testable], server: Server) {
+    let options = match Options::from_args() {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(101);
+        }
+    };
 
-            for test_result in &test_results {
-                println!("{}", test_result);
+    let tests: Vec<&'static dyn Testable> = tests
+        .iter()
+        .copied()
+        .chain(registry::registered())
+        .collect();
+    let total = tests.len();
+    let tests: Vec<&'static dyn Testable> = tests
+        .into_iter()
+        .filter(|test| {
+            options.is_selected(test.name())
+                && options.has_selected_tags(test.meta().tags)
+                && (!options.ignored || test.meta().ignore.is_some())
+        })
+        .collect();
+    let filtered_out = total - tests.len();
+
+    if options.help {
+        print!("{}", options::USAGE);
+        return;
+    }
+
+    if options.list {
+        for test in &tests {
+            println!("{}: test", test.name());
+        }
+        println!("\n{} tests", tests.len());
+        return;
+    }
+
+    let mut server = match (&options.server_url, &options.server_config) {
+        (Some(url), _) => Server::External(url.clone()),
+        (None, Some(path)) => match Server::from_file(path) {
+            Ok(server) => server,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(101);
             }
+        },
+        (None, None) => server,
+    };
+    if let Server::Launch(mut launcher) = server {
+        if let Some(timeout) = options.server_timeout {
+            launcher = launcher.timeout(timeout);
+        }
+        if let Some(path) = &options.server_health_path {
+            launcher = launcher.health_path(path.as_str());
+        }
+        server = Server::Launch(launcher);
+    }
+
+    capture::install_loggers(options.log_level);
+
+    let artifacts = match Artifacts::new(&options.artifacts_dir) {
+        Ok(artifacts) => artifacts,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(101);
+        }
+    };
+
+    let reporter: Mutex<Box<dyn Reporter>> = Mutex::new(match options.format {
+        Format::Pretty => Box::new(PrettyReporter {
+            slowest: options.slowest,
+        }),
+        Format::Json => Box::new(JsonReporter),
+    });
+    reporter
+        .lock()
+        .unwrap()
+        .suite_started(tests.len(), &options.browsers);
 
-            let successes = test_results
+    let started = Instant::now();
+    let mut web_server = None;
+    let runtime = Runtime::new().unwrap();
+    let results = runtime.block_on(async {
+        let (base_url, server_log) = match &server {
+            Server::Launch(launcher) => {
+                let web_server =
+                    web_server.insert(launcher.launch().map_err(|error| FailedToLaunch {
+                        program: launcher.program().to_string_lossy().into_owned(),
+                        error,
+                    })?);
+                web_server.wait_until_ready().await?;
+                (
+                    web_server.base_url().to_string(),
+                    Some(web_server.log().clone()),
+                )
+            }
+            Server::External(url) => (url.trim_end_matches('/').to_string(), None),
+        };
+        let run = Run {
+            options: &options,
+            base_url: &base_url,
+            artifacts: &artifacts,
+            server_log,
+            hooks: Hooks::registered(),
+            fixtures: Arc::default(),
+            browser_fixtures: options
+                .browsers
                 .iter()
-                .filter(|test_result| test_result.result.is_ok())
-                .count();
-            if successes == test_results.len() {
-                println!("{} tests ran with success", successes);
-                0
-            } else {
-                println!("{} errors", test_results.len() - successes);
-                1
+                .map(|&browser_type| (browser_type, Arc::default()))
+                .collect(),
+        };
+        run_tests(&tests, &run, &reporter).await
+    });
+
+    let mut reporter = reporter.into_inner().unwrap();
+    let exit_code = match results {
+        Ok((test_results, run_errors)) => {
+            let duration = started.elapsed();
+            reporter.suite_finished(&Summary {
+                results: &test_results,
+                filtered_out,
+                duration,
+            });
+
+            let any = |status| {
+                test_results
+                    .iter()
+                    .any(|test_result| test_result.status() == status)
+            };
+            let mut exit_code =
+                if any(TestStatus::Failed) || (any(TestStatus::Flaky) && options.fail_on_flaky) {
+                    1
+                } else {
+                    0
+                };
+            let reports: [(&Option<PathBuf>, WriteReport); 2] = [
+                (&options.junit, report::junit::write_report),
+                (&options.html, report::html::write_report),
+            ];
+            for (path, write_report) in reports {
+                let path = match path {
+                    Some(path) => path,
+                    None => continue,
+                };
+                if let Err(error) = write_report(path, &options.targets(), &test_results, duration)
+                {
+                    reporter.error(
+                        &FailedToWriteReport {
+                            path: path.clone(),
+                            error,
+                        }
+                        .into(),
+                    );
+                    exit_code = 1;
+                }
             }
+            for error in &run_errors {
+                reporter.error(error);
+                exit_code = 1;
+            }
+            exit_code
         }
         Err(error) => {
-            println!("{:#?}", error);
+            reporter.error(&error);
             1
         }
     };
 
-    web_server.kill().unwrap();
+    if let Some(mut web_server) = web_server {
+        web_server.kill().unwrap();
+    }
 
     std::process::exit(exit_code);
 }
 
-async fn run_tests(tests: &[&dyn Testable]) -> anyhow::Result<Vec<TestResult>> {
+type WriteReport = fn(&Path, &[Target], &[TestResult], Duration) -> std::io::Result<()>;
+
+/// Runs the tests in every browser, returning their results and the failures of the hooks and
+/// fixtures they shared
+async fn run_tests(
+    tests: &[&'static dyn Testable],
+    run: &Run<'_>,
+    reporter: &Mutex<Box<dyn Reporter>>,
+) -> anyhow::Result<(Vec<TestResult>, Vec<anyhow::Error>)> {
+    let options = run.options;
+    // Tests don't run as the devices a browser can't emulate, nor in the browser if it can't
+    // emulate any of the devices of the run
+    for (browser_type, device) in options.skipped_targets() {
+        reporter
+            .lock()
+            .unwrap()
+            .device_skipped(browser_type, device);
+    }
+    let targets = options.targets();
+    if let Some(&browser_type) = options.browsers.iter().find(|&&browser_type| {
+        !targets
+            .iter()
+            .any(|target| target.browser_type == browser_type)
+    }) {
+        return Err(NoDeviceEmulated(browser_type).into());
+    }
+
     let playwright = Playwright::initialize().await?;
-    playwright.prepare()?; // Install browsers
 
     let mut browser_map = HashMap::new();
     let mut initialization_errors: Option<ErrorList<FailedToInitialize>> = None;
-    {
-        match playwright
-            .chromium()
-            .launcher()
-            .headless(true)
-            .launch()
-            .await
-        {
+    for &browser_type in &options.browsers {
+        match launch_browser(&playwright, browser_type).await {
             Ok(browser) => {
-                browser_map.insert(BrowserType::Chromium, browser);
+                browser_map.insert(browser_type, browser);
             }
-            Err(err) => {
-                if let Some(errs) = &mut initialization_errors {
-                    errs.push(FailedToInitialize(BrowserType::Chromium), err);
-                } else {
-                    initialization_errors = Some(ErrorList::new(
-                        FailedToInitialize(BrowserType::Chromium),
-                        err,
-                    ));
-                }
-            }
-        }
-    }
-    {
-        match playwright
-            .firefox()
-            .launcher()
-            .headless(true)
-            .launch()
-            .await
-        {
-            Ok(browser) => {
-                browser_map.insert(BrowserType::Firefox, browser);
+            Err(err) if options.skip_missing_browsers => {
+                reporter.lock().unwrap().browser_skipped(browser_type, &err);
             }
             Err(err) => {
                 if let Some(errs) = &mut initialization_errors {
-                    errs.push(FailedToInitialize(BrowserType::Firefox), err);
-                } else {
-                    initialization_errors = Some(ErrorList::new(
-                        FailedToInitialize(BrowserType::Firefox),
-                        err,
-                    ));
-                }
-            }
-        }
-    }
-    {
-        match playwright.webkit().launcher().headless(true).launch().await {
-            Ok(browser) => {
-                browser_map.insert(BrowserType::Webkit, browser);
-            }
-            Err(err) => {
-                if let Some(errs) = &mut initialization_errors {
-                    errs.push(FailedToInitialize(BrowserType::Webkit), err);
+                    errs.push(FailedToInitialize(browser_type), err);
                 } else {
                     initialization_errors =
-                        Some(ErrorList::new(FailedToInitialize(BrowserType::Webkit), err));
+                        Some(ErrorList::new(FailedToInitialize(browser_type), err));
                 }
             }
         }
@@ -139,42 +329,155 @@ async fn run_tests(tests: &[&dyn Testable]) -> anyhow::Result<Vec<TestResult>> {
     if let Some(errors) = initialization_errors {
         return Err(errors.into());
     }
+    if browser_map.is_empty() {
+        return Err(NoBrowserAvailable.into());
+    }
+
+    // Iterate in the order the browsers were given so `--serial` runs are reproducible
+    let browsers: Vec<_> = options
+        .browsers
+        .iter()
+        .filter_map(|browser_type| browser_map.get_key_value(browser_type))
+        .collect();
+    let browser_slots: HashMap<BrowserType, Semaphore> = match options.jobs_per_browser {
+        Some(jobs) => browsers
+            .iter()
+            .map(|(&browser_type, _)| (browser_type, Semaphore::new(jobs)))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    // The tests of a browser don't run if a hook they depend on failed
+    let mut run_errors = Vec::new();
+    let run_fixtures = run.fixtures_of(None);
+    let run_ctx = FixtureContext::shared(run.base_url, None, &run_fixtures);
+    let failed_hook = match run.hooks.run_before(When::BeforeAll, &run_ctx).await {
+        Ok(()) => None,
+        Err((hook, err)) => {
+            run_errors.push(err);
+            Some(hook)
+        }
+    };
+    let mut failed_hooks: HashMap<BrowserType, &'static Hook> = HashMap::new();
+    for &(&browser_type, browser) in &browsers {
+        if let Some(hook) = failed_hook {
+            failed_hooks.insert(browser_type, hook);
+            continue;
+        }
+        let fixtures = run.fixtures_of(Some(browser_type));
+        let ctx = FixtureContext::shared(run.base_url, Some((browser_type, browser)), &fixtures);
+        if let Err((hook, err)) = run.hooks.run_before(When::BeforeAll, &ctx).await {
+            run_errors.push(err);
+            failed_hooks.insert(browser_type, hook);
+        }
+    }
 
+    let targets: Vec<(Target, &Browser)> = targets
+        .into_iter()
+        .filter_map(|target| Some((target, browser_map.get(&target.browser_type)?)))
+        .collect();
     let (results, error_list) = stream::iter(tests)
         .flat_map(|test| {
-            stream::iter(browser_map.iter()).map(move |(&browser_type, browser)| async move {
-                let context = browser.context_builder().build().await.map_err(|err| {
-                    (
-                        FailedToOpenPage {
-                            browser_type,
+            let browser_slots = &browser_slots;
+            let failed_hooks = &failed_hooks;
+            let meta = test.meta();
+            stream::iter(targets.iter())
+                .filter(move |(target, _)| future::ready(meta.runs_in(target.browser_type)))
+                .map(move |&(target, browser)| async move {
+                    let Target {
+                        browser_type,
+                        device,
+                    } = target;
+                    let run_ignored = options.ignored || options.include_ignored;
+                    if let Some(reason) = meta.ignore.filter(|_| !run_ignored) {
+                        let test_result = TestResult {
                             test_name: test.name(),
-                        },
-                        err,
-                    )
-                })?;
-                let page = context.new_page().await.map_err(|err| {
-                    (
-                        FailedToOpenPage {
                             browser_type,
+                            device,
+                            ignored: Some(reason),
+                            expected_failure: false,
+                            attempts: Vec::new(),
+                        };
+                        reporter.lock().unwrap().test_finished(&test_result);
+                        return Ok(test_result);
+                    }
+                    if let Some(&hook) = failed_hooks.get(&browser_type) {
+                        let test_result = TestResult {
                             test_name: test.name(),
-                        },
-                        err,
-                    )
-                })?;
-                let test_name = test.name();
-                Ok(test
-                    .run(Context { page })
-                    .map(|(result, output)| TestResult {
-                        test_name,
+                            browser_type,
+                            device,
+                            ignored: None,
+                            expected_failure: false,
+                            attempts: vec![Attempt {
+                                outcome: TestOutcome::Failed(NotRun(hook).into()),
+                                output: Vec::new(),
+                                page_messages: Vec::new(),
+                                server_log: Vec::new(),
+                                started_at: SystemTime::now(),
+                                duration: Duration::ZERO,
+                                artifacts: Vec::new(),
+                            }],
+                        };
+                        reporter.lock().unwrap().test_finished(&test_result);
+                        return Ok(test_result);
+                    }
+                    // Known failures are not retried, and passing is what fails them
+                    let expected_failure = meta.is_expected_to_fail_in(browser_type);
+                    let retries = match meta.retries {
+                        _ if expected_failure => 0,
+                        Some(retries) => retries,
+                        None => options.retries,
+                    };
+
+                    let _slot = match browser_slots.get(&browser_type) {
+                        Some(slots) => Some(slots.acquire().await.unwrap()),
+                        None => None,
+                    };
+                    reporter.lock().unwrap().test_started(test.name(), target);
+
+                    let mut attempts = Vec::new();
+                    let mut failed_as_expected = false;
+                    loop {
+                        let mut attempt =
+                            run_attempt(*test, target, browser, attempts.len() + 1, run)
+                                .await
+                                .map_err(|err| {
+                                    (
+                                        FailedToOpenPage {
+                                            target,
+                                            test_name: test.name(),
+                                        },
+                                        err,
+                                    )
+                                })?;
+                        if expected_failure {
+                            if attempt.outcome.is_success() {
+                                attempt.outcome =
+                                    TestOutcome::Failed(UnexpectedPass(browser_type).into());
+                            } else {
+                                failed_as_expected = true;
+                            }
+                        }
+                        let passed = attempt.outcome.is_success();
+                        attempts.push(attempt);
+                        if passed || attempts.len() > retries {
+                            break;
+                        }
+                    }
+
+                    let test_result = TestResult {
+                        test_name: test.name(),
                         browser_type,
-                        result,
-                        output,
-                    })
-                    .inspect(|test_result| println!("{}", test_result))
-                    .await)
-            })
+                        device,
+                        ignored: None,
+                        expected_failure: failed_as_expected,
+                        attempts,
+                    };
+                    reporter.lock().unwrap().test_finished(&test_result);
+                    Ok(test_result)
+                })
         })
-        .buffer_unordered(tests.len() * browser_map.len())
+        .buffer_unordered(options.jobs)
         .fold(
             (Vec::new(), None),
             |(mut test_results, errors), result| async {
@@ -195,48 +498,308 @@ async fn run_tests(tests: &[&dyn Testable]) -> anyhow::Result<Vec<TestResult>> {
         )
         .await;
 
+    // While the browsers are still open
+    for &(&browser_type, browser) in &browsers {
+        let fixtures = run.fixtures_of(Some(browser_type));
+        let ctx = FixtureContext::shared(run.base_url, Some((browser_type, browser)), &fixtures);
+        run.hooks
+            .run_after(When::AfterAll, &ctx, options.test_timeout, &mut run_errors)
+            .await;
+        fixtures
+            .browser
+            .teardown(options.test_timeout, &mut run_errors)
+            .await;
+    }
+    run.hooks
+        .run_after(
+            When::AfterAll,
+            &run_ctx,
+            options.test_timeout,
+            &mut run_errors,
+        )
+        .await;
+    run.fixtures
+        .teardown(options.test_timeout, &mut run_errors)
+        .await;
+
     if let Some(error_list) = error_list {
         return Err(error_list.into());
     }
 
-    Ok(results)
+    Ok((results, run_errors))
 }
 
-struct TestResult {
-    test_name: &'static str,
-    browser_type: BrowserType,
-    result: anyhow::Result<()>,
-    output: Vec<u8>,
+/// What the attempts of a run share
+struct Run<'a> {
+    options: &'a Options,
+    base_url: &'a str,
+    artifacts: &'a Artifacts,
+    /// Output of the server launched for the run, if any
+    server_log: Option<ServerLog>,
+    hooks: Hooks,
+    fixtures: Arc<FixtureStore>,
+    browser_fixtures: HashMap<BrowserType, Arc<FixtureStore>>,
 }
 
-impl Display for TestResult {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match &self.result {
-            Ok(()) => write!(f, "{} in {}...\t[OK]", self.test_name, self.browser_type)?,
-            Err(err) => write!(
-                f,
-                "{} in {}...\t[FAILED]\n{:#?}",
-                self.test_name, self.browser_type, err
-            )?,
+impl Run<'_> {
+    /// The fixtures of a new attempt in `browser_type`, or of the run
+    fn fixtures_of(&self, browser_type: Option<BrowserType>) -> Fixtures {
+        Fixtures {
+            run: self.fixtures.clone(),
+            browser: browser_type
+                .and_then(|browser_type| self.browser_fixtures.get(&browser_type).cloned())
+                .unwrap_or_default(),
+            test: Arc::default(),
         }
-        if !self.output.is_empty() {
-            write!(
-                f,
-                "\n   ----- TEST STDOUT -----   \n{}\n",
-                String::from_utf8_lossy(&self.output)
-            )?;
+    }
+}
+
+/// Runs the test once in a new context of `browser`, emulating the device of `target` if any,
+/// `attempt` counting from 1
+async fn run_attempt(
+    test: &'static dyn Testable,
+    target: Target,
+    browser: &Browser,
+    attempt: usize,
+    run: &Run<'_>,
+) -> Result<Attempt, Arc<playwright::Error>> {
+    let Run {
+        options,
+        base_url,
+        artifacts,
+        ..
+    } = *run;
+    let browser_type = target.browser_type;
+    let test_id = match target.device {
+        Some(device) => format!("{}@{}/{}#{}", test.name(), browser_type, device, attempt),
+        None => format!("{}@{}#{}", test.name(), browser_type, attempt),
+    };
+
+    // Videos get a random name until we know whether the attempt failed
+    let video_dir = artifacts.test_dir(test.name(), target);
+    let mut context_builder = browser.context_builder();
+    // The options of the run and of the test apply over the device
+    if let Some(device) = &target.device {
+        context_builder = device.configure(context_builder);
+    }
+    let context_options = test.meta().context.over(&options.context);
+    let mut headers = context_options.extra_http_headers.clone();
+    // Only the log of a server launched by the runner is read. Playwright can't route requests
+    // yet, so the header goes to every origin the pages talk to, see `--no-test-id-header`
+    if run.server_log.is_some() && options.test_id_header {
+        headers.insert(TEST_ID_HEADER.to_string(), test_id.clone());
+    }
+    let mut context_builder = context_options
+        .configure(context_builder)
+        .extra_http_headers(headers);
+    if options.video != Video::Off {
+        context_builder = context_builder.record_video(RecordVideo {
+            dir: &video_dir,
+            size: None,
+        });
+    }
+    let context = context_builder.build().await?;
+    ConsoleCollector::install(&context).await?;
+    let page = context.new_page().await?;
+    let console = ConsoleCollector::start(&page)?;
+    let fixtures = run.fixtures_of(Some(browser_type));
+    let fixture_ctx = FixtureContext::new(base_url, browser_type, browser, &page, &fixtures);
+
+    let started_at = SystemTime::now();
+    let started = Instant::now();
+    let test_timeout = test.meta().timeout.unwrap_or(options.test_timeout);
+    let deadline = Deadline::new(test_timeout);
+    let pending = PendingAwaits::default();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let running = async {
+        for fixture in test.meta().fixtures {
+            let label = format!("setup of fixture {}", fixture.name());
+            labelled(label, fixture.setup(&fixture_ctx)).await?;
+        }
+        if let Err((_, err)) = run.hooks.run_before(When::BeforeEach, &fixture_ctx).await {
+            return Err(err);
+        }
+        capture::run_on_test_thread(test.name(), output.clone(), {
+            let ctx = Context {
+                page: page.clone(),
+                base_url: base_url.to_string(),
+                deadline: deadline.clone(),
+                fixtures: fixtures.clone(),
+                target,
+            };
+            let pending = pending.clone();
+            move || pending.track(test.run(ctx))
+        })
+        .await?
+    };
+    let outcome = match future::select(
+        Box::pin(pending.track(running)),
+        Box::pin(deadline.elapsed()),
+    )
+    .await
+    {
+        Either::Left((result, _)) => TestOutcome::from(result),
+        // Dropping the test cancels it, and the tasks it spawned
+        Either::Right(((), _)) => TestOutcome::TimedOut(TimedOut {
+            timeout: deadline.timeout(),
+            pending: pending.labels(),
+        }),
+    };
+    let mut output = mem::take(&mut *output.lock().unwrap());
+    let duration = started.elapsed();
+
+    let mut attempt_artifacts = Vec::new();
+    if !outcome.is_success() {
+        let path = artifacts.path(test.name(), target, attempt, "screenshot.png");
+        if save_screenshot(&page, artifacts, &path).await.is_ok() {
+            attempt_artifacts.push(path);
+        }
+    }
+    // Before closing the page, which they may use
+    let mut cleanup_errors = Vec::new();
+    run.hooks
+        .run_after(
+            When::AfterEach,
+            &fixture_ctx,
+            test_timeout,
+            &mut cleanup_errors,
+        )
+        .await;
+    fixtures
+        .test
+        .teardown(test_timeout, &mut cleanup_errors)
+        .await;
+    let mut outcome = outcome;
+    for error in cleanup_errors {
+        match outcome {
+            TestOutcome::Passed => outcome = TestOutcome::Failed(error),
+            _ => {
+                let _ = writeln!(output, "{:#}", error);
+            }
+        }
+    }
+
+    let recorded_video = page
+        .video()
+        .ok()
+        .flatten()
+        .and_then(|video| video.path().ok());
+    // The test is dropped by now, closing the context also closes its pages and finishes writing
+    // their videos. Bounded like the screenshot, a page hung by a timed out test may not close
+    let _ = tokio::time::timeout(Duration::from_secs(5), context.close()).await;
+    let page_messages = console.finish().await;
+    let finished_at = SystemTime::now();
+
+    let outcome = match outcome {
+        TestOutcome::Passed if options.fail_on_page_error => {
+            let errors: Vec<String> = page_messages
+                .iter()
+                .filter_map(|message| match message {
+                    PageMessage::Error(error) => Some(error.clone()),
+                    _ => None,
+                })
+                .collect();
+            if errors.is_empty() {
+                TestOutcome::Passed
+            } else {
+                TestOutcome::Failed(UncaughtPageError(errors).into())
+            }
+        }
+        outcome => outcome,
+    };
+
+    let server_log = match &run.server_log {
+        Some(server_log) if !outcome.is_success() => {
+            server_log.lines_of_test(&test_id, started_at, finished_at)
+        }
+        _ => Vec::new(),
+    };
+
+    if let Some(recorded_video) = recorded_video {
+        let keep = options.video == Video::On || !outcome.is_success();
+        if keep {
+            let path = artifacts.path(test.name(), target, attempt, "video.webm");
+            if fs::rename(&recorded_video, &path).is_ok() {
+                attempt_artifacts.push(path);
+            }
+        } else {
+            let _ = fs::remove_file(&recorded_video);
+            // Only removed when no other artifact was saved for the test
+            let _ = fs::remove_dir(&video_dir);
         }
-        Ok(())
     }
+
+    Ok(Attempt {
+        outcome,
+        output,
+        page_messages,
+        server_log,
+        started_at,
+        duration,
+        artifacts: attempt_artifacts,
+    })
+}
+
+/// Saves a screenshot of the whole page, giving up after a few seconds in case the page hangs
+async fn save_screenshot(page: &Page, artifacts: &Artifacts, path: &Path) -> Result<()> {
+    let screenshot = page
+        .screenshot_builder()
+        .full_page(true)
+        .timeout(5_000.0)
+        .screenshot()
+        .await?;
+    artifacts.save(path, &screenshot)?;
+    Ok(())
 }
 
-#[derive(Copy, Clone, PartialOrd, PartialEq, Eq, Hash)]
-enum BrowserType {
+/// Installs the browser if needed, then launches it
+async fn launch_browser(playwright: &Playwright, browser_type: BrowserType) -> Result<Browser> {
+    let launcher = match browser_type {
+        BrowserType::Chromium => {
+            playwright.install_chromium()?;
+            playwright.chromium()
+        }
+        BrowserType::Firefox => {
+            playwright.install_firefox()?;
+            playwright.firefox()
+        }
+        BrowserType::Webkit => {
+            playwright.install_webkit()?;
+            playwright.webkit()
+        }
+    };
+
+    Ok(launcher.launcher().headless(true).launch().await?)
+}
+
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Eq, Hash)]
+pub enum BrowserType {
     Chromium,
     Firefox,
     Webkit,
 }
 
+impl BrowserType {
+    pub const ALL: [BrowserType; 3] = [
+        BrowserType::Chromium,
+        BrowserType::Firefox,
+        BrowserType::Webkit,
+    ];
+}
+
+impl FromStr for BrowserType {
+    type Err = OptionsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "chromium" => Ok(BrowserType::Chromium),
+            "firefox" => Ok(BrowserType::Firefox),
+            "webkit" => Ok(BrowserType::Webkit),
+            _ => Err(OptionsError::UnknownBrowser(s.to_string())),
+        }
+    }
+}
+
 impl Display for BrowserType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -253,12 +816,69 @@ impl Display for BrowserType {
 
 pub struct Context {
     pub page: Page,
+    base_url: String,
+    deadline: Deadline,
+    fixtures: Fixtures,
+    target: Target,
+}
+
+impl Context {
+    /// URL of the server under test, without trailing slash, e.g. `http://127.0.0.1:38279`
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn browser_type(&self) -> BrowserType {
+        self.target.browser_type
+    }
+
+    /// The device the browser emulates, if the run has some, e.g. to open the menu of the
+    /// mobile layout
+    pub fn device(&self) -> Option<Device> {
+        self.target.device
+    }
+
+    /// Absolute URL of `path` on the server under test
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// Navigates the page to `path` on the server under test
+    pub async fn goto(&self, path: &str) -> Result<Option<Response>> {
+        let url = self.url(path);
+        let response = labelled(format!("goto {}", url), self.page.goto_builder(&url).goto());
+        Ok(response.await?)
+    }
+
+    /// Timeout of the test, counted from its start
+    pub fn timeout(&self) -> Duration {
+        self.deadline.timeout()
+    }
+
+    /// Overrides the timeout of the test, e.g. for a scenario known to be slow. It is counted
+    /// from the start of the test, not from this call.
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.deadline.set_timeout(timeout)
+    }
+
+    /// A fixture the test declared, see [`fixture`]
+    pub fn fixture<T: Fixture>(&self) -> Result<Arc<T>> {
+        self.fixtures
+            .get::<T>()
+            .ok_or_else(|| FixtureNotDeclared(type_name::<T>()).into())
+    }
 }
 
 #[async_trait]
-pub trait Testable {
+pub trait Testable: Sync {
     fn name(&self) -> &'static str;
-    async fn run(&self, ctx: Context) -> (Result<()>, Vec<u8>);
+
+    /// Tags, browsers, timeout, ... of the test, see [`meta`]
+    fn meta(&self) -> TestMeta {
+        TestMeta::new()
+    }
+
+    async fn run(&self, ctx: Context) -> Result<()>;
 }
 
 #[async_trait]
@@ -271,54 +891,8 @@ where
         type_name::<Self>()
     }
 
-    async fn run(&self, ctx: Context) -> (Result<()>, Vec<u8>) {
-        let (result, output) = CaptureOutputFuture::new(self(ctx)).await;
-        match result {
-            Ok(test_result) => (test_result, output),
-            Err(caught_panic) => (Err(caught_panic.into()), output),
-        }
-    }
-}
-
-#[pin_project]
-#[must_use = "futures do nothing unless you `.await` or poll them"]
-struct CaptureOutputFuture<Fut> {
-    #[pin]
-    future: Fut,
-    output: Arc<Mutex<Vec<u8>>>,
-}
-
-impl<Fut> CaptureOutputFuture<Fut> {
-    fn new(future: Fut) -> Self {
-        CaptureOutputFuture {
-            future,
-            output: Arc::new(Mutex::new(Vec::new())),
-        }
-    }
-}
-
-impl<Fut: Future> Future for CaptureOutputFuture<Fut> {
-    type Output = (Result<Fut::Output, CaughtPanic>, Vec<u8>);
-
-    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
-        std::io::set_output_capture(Some(Arc::clone(&self.output)));
-        let this = self.project();
-        let f = this.future;
-        let o = this.output;
-        let result = catch_unwind(AssertUnwindSafe(|| f.poll(cx)));
-        std::io::set_output_capture(None);
-
-        match result {
-            Ok(Poll::Pending) => Poll::Pending,
-            Ok(Poll::Ready(value)) => Poll::Ready((
-                Ok(value),
-                std::mem::replace(o.lock().unwrap().as_mut(), Vec::new()),
-            )),
-            Err(err) => Poll::Ready((
-                Err(CaughtPanic::new(err)),
-                std::mem::replace(o.lock().unwrap().as_mut(), Vec::new()),
-            )),
-        }
+    async fn run(&self, ctx: Context) -> Result<()> {
+        catch_panic(self(ctx)).await
     }
 }
 
@@ -368,9 +942,101 @@ impl Display for FailedToInitialize {
     }
 }
 
+#[derive(Debug)]
+struct NoBrowserAvailable;
+
+impl Display for NoBrowserAvailable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No browser available to run the tests")
+    }
+}
+
+impl Error for NoBrowserAvailable {}
+
+#[derive(Debug)]
+struct NoDeviceEmulated(BrowserType);
+
+impl Display for NoDeviceEmulated {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} can't emulate any of the devices of the run, see --device",
+            self.0
+        )
+    }
+}
+
+impl Error for NoDeviceEmulated {}
+
+struct FailedToLaunch {
+    program: String,
+    error: std::io::Error,
+}
+
+impl Display for FailedToLaunch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to launch {}: {}", self.program, self.error)
+    }
+}
+
+impl Debug for FailedToLaunch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for FailedToLaunch {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The page threw errors it didn't catch, while the test itself passed
+struct UncaughtPageError(Vec<String>);
+
+impl Display for UncaughtPageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Uncaught error in the page:")?;
+        for error in &self.0 {
+            write!(f, "\n\t- {}", error.replace('\n', "\n\t  "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for UncaughtPageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for UncaughtPageError {}
+
+/// A test passed in a browser it is declared to fail in, see [`TestMeta::expected_failure_in`]
+struct UnexpectedPass(BrowserType);
+
+impl Display for UnexpectedPass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The test passed in {} but is expected to fail there, remove it from \
+             `expected_failure_in` if it was fixed",
+            self.0
+        )
+    }
+}
+
+impl Debug for UnexpectedPass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for UnexpectedPass {}
+
 struct FailedToOpenPage {
     test_name: &'static str,
-    browser_type: BrowserType,
+    target: Target,
 }
 
 impl Display for FailedToOpenPage {
@@ -378,11 +1044,20 @@ impl Display for FailedToOpenPage {
         write!(
             f,
             "Failed to open page in {} of {}",
-            self.browser_type, self.test_name
+            self.target, self.test_name
         )
     }
 }
 
+/// Awaits `future`, turning a panic into a [`CaughtPanic`] error so that it fails what it ran
+/// rather than the whole run
+pub(crate) async fn catch_panic<T, F: Future<Output = Result<T>>>(future: F) -> Result<T> {
+    match AssertUnwindSafe(future).catch_unwind().await {
+        Ok(result) => result,
+        Err(panic) => Err(CaughtPanic::new(panic).into()),
+    }
+}
+
 struct CaughtPanic(Option<Box<str>>);
 
 impl CaughtPanic {
@@ -413,34 +1088,3 @@ impl Display for CaughtPanic {
 }
 
 impl Error for CaughtPanic {}
-
-#[test_case]
-async fn sleep(_ctx: Context) -> Result<()> {
-    use std::time::Duration;
-    tokio::time::sleep(Duration::from_millis(1000)).await;
-    Ok(())
-}
-
-// #[test_case]
-// async fn err(_ctx: Context) -> Result<()> {
-//     use std::str::FromStr;
-//     let _ = i32::from_str("Not a number")?;
-//     Ok(())
-// }
-//
-// #[test_case]
-// async fn unimplemented(_ctx: Context) -> Result<()> {
-//     unimplemented!()
-// }
-
-#[test_case]
-async fn hello_world(ctx: Context) -> Result<()> {
-    ctx.page
-        .goto_builder("http://127.0.0.1:8000")
-        .goto()
-        .await?;
-    let body = ctx.page.inner_text("body", None).await?;
-    assert_eq!(body, "Hello, world!");
-
-    Ok(())
-}
@@ -0,0 +1,201 @@
+//! What the runner needs to know about a test besides its code: tags to select it with, whether
+//! it is ignored, which browsers it runs in and how long it may take.
+//!
+//! A plain `async fn` has the default metadata. The simplest way to declare some is the
+//! [`#[e2e::test]`](crate::test) attribute:
+//!
+//! ```ignore
+//! #[e2e::test(browsers = [chromium], tags = ["visual"], timeout = "60s")]
+//! async fn dark_mode(ctx: Context) -> Result<()> {
+//!     ...
+//! }
+//! ```
+//!
+//! which wraps the function in a [`Test`] built with the same metadata, e.g.
+//! `Test::new(dark_mode).tags(&["visual"])`, and registers it with the runner when the binary is
+//! loaded, see [`registry`](crate::registry).
+
+use crate::{context_options::ContextOptions, fixture::FixtureRef, BrowserType, Context, Testable};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{any::type_name, future::Future, time::Duration};
+
+#[derive(Debug, Copy, Clone)]
+pub struct TestMeta {
+    /// Labels to select the test with `--tags` and `--exclude-tags`
+    pub tags: &'static [&'static str],
+    /// Why the test is not run, if it is ignored, unless with `--ignored` or `--include-ignored`
+    pub ignore: Option<&'static str>,
+    /// Browsers the test runs in, all of them when empty
+    pub only_in: &'static [BrowserType],
+    /// Browsers the test is known to fail in: it doesn't fail the run there, unless it passes
+    pub expected_failure_in: &'static [BrowserType],
+    /// Overrides `--test-timeout`
+    pub timeout: Option<Duration>,
+    /// Overrides `--retries`
+    pub retries: Option<usize>,
+    /// Fixtures set up before the test, see [`fixture`](crate::fixture)
+    pub fixtures: &'static [FixtureRef],
+    /// Options of its browser context, over the ones of the run, see
+    /// [`context_options`](crate::context_options)
+    pub context: ContextOptions,
+}
+
+impl TestMeta {
+    pub const fn new() -> Self {
+        TestMeta {
+            tags: &[],
+            ignore: None,
+            only_in: &[],
+            expected_failure_in: &[],
+            timeout: None,
+            retries: None,
+            fixtures: &[],
+            context: ContextOptions::new(),
+        }
+    }
+
+    pub fn runs_in(&self, browser_type: BrowserType) -> bool {
+        self.only_in.is_empty() || self.only_in.contains(&browser_type)
+    }
+
+    pub fn is_expected_to_fail_in(&self, browser_type: BrowserType) -> bool {
+        self.expected_failure_in.contains(&browser_type)
+    }
+}
+
+impl Default for TestMeta {
+    fn default() -> Self {
+        TestMeta::new()
+    }
+}
+
+/// A test function with its metadata, see the [module documentation](self)
+pub struct Test<F> {
+    test: F,
+    name: Option<&'static str>,
+    meta: TestMeta,
+}
+
+impl<F> Test<F> {
+    pub const fn new(test: F) -> Self {
+        Test {
+            test,
+            name: None,
+            meta: TestMeta::new(),
+        }
+    }
+
+    /// Name of the test in filters and reports, the path of its function unless set
+    pub const fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub const fn tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.meta.tags = tags;
+        self
+    }
+
+    pub const fn ignore(mut self, reason: &'static str) -> Self {
+        self.meta.ignore = Some(reason);
+        self
+    }
+
+    pub const fn only_in(mut self, browsers: &'static [BrowserType]) -> Self {
+        self.meta.only_in = browsers;
+        self
+    }
+
+    pub const fn expected_failure_in(mut self, browsers: &'static [BrowserType]) -> Self {
+        self.meta.expected_failure_in = browsers;
+        self
+    }
+
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.meta.timeout = Some(timeout);
+        self
+    }
+
+    pub const fn retries(mut self, retries: usize) -> Self {
+        self.meta.retries = Some(retries);
+        self
+    }
+
+    pub const fn fixtures(mut self, fixtures: &'static [FixtureRef]) -> Self {
+        self.meta.fixtures = fixtures;
+        self
+    }
+
+    pub const fn context(mut self, context: ContextOptions) -> Self {
+        self.meta.context = context;
+        self
+    }
+}
+
+#[async_trait]
+impl<F, FF> Testable for Test<F>
+where
+    F: Fn(Context) -> FF + Sync,
+    FF: Future<Output = Result<()>> + Send,
+{
+    fn name(&self) -> &'static str {
+        self.name.unwrap_or_else(type_name::<F>)
+    }
+
+    fn meta(&self) -> TestMeta {
+        self.meta
+    }
+
+    async fn run(&self, ctx: Context) -> Result<()> {
+        self.test.run(ctx).await
+    }
+}
+
+/// `path` without its first segment, e.g. `tests::login` for `my_crate::tests::login`
+#[doc(hidden)]
+pub const fn without_crate_name(path: &'static str) -> &'static str {
+    let bytes = path.as_bytes();
+    let mut index = 0;
+    while index + 1 < bytes.len() {
+        if bytes[index] == b':' && bytes[index + 1] == b':' {
+            return match std::str::from_utf8(bytes.split_at(index + 2).1) {
+                Ok(rest) => rest,
+                Err(_) => path,
+            };
+        }
+        index += 1;
+    }
+    path
+}
+
+/// What a `static` declaring a [`Test`] holds, so that its type doesn't have to be spelled
+#[async_trait]
+impl Testable for &'static dyn Testable {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn meta(&self) -> TestMeta {
+        (**self).meta()
+    }
+
+    async fn run(&self, ctx: Context) -> Result<()> {
+        (**self).run(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_crate_name() {
+        assert_eq!(without_crate_name("end_to_end::hello_world"), "hello_world");
+        assert_eq!(
+            without_crate_name("e2e::tests::fetch::get_404"),
+            "tests::fetch::get_404"
+        );
+        assert_eq!(without_crate_name("hello_world"), "hello_world");
+    }
+}
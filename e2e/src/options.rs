@@ -0,0 +1,869 @@
+use crate::{
+    context_options::{parse_color_scheme, ContextOptions},
+    device::{Device, Target},
+    BrowserType,
+};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    num::NonZeroUsize,
+    path::PathBuf,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+/// Command-line options of the test runner, parsed from the arguments given after `--` in
+/// `cargo test -- <args>`. The syntax mirrors libtest so the usual muscle memory still works:
+/// the libtest flags that don't apply here, e.g. `--nocapture`, `--quiet` or `--color`, are
+/// accepted and ignored.
+///
+/// Some options can also be set through `E2E_*` environment variables, command-line arguments
+/// take precedence over them.
+#[derive(Debug)]
+pub struct Options {
+    /// Only run tests whose name matches one of these filters
+    pub filters: Vec<String>,
+    /// Skip tests whose name matches one of these filters
+    pub skip: Vec<String>,
+    /// Match filters against the whole test name instead of a substring
+    pub exact: bool,
+    /// Only run tests with one of these tags, see [`TestMeta::tags`](crate::meta::TestMeta::tags)
+    /// (`--tags a,b` / `E2E_TAGS`)
+    pub tags: Vec<String>,
+    /// Skip tests with one of these tags (`--exclude-tags a,b` / `E2E_EXCLUDE_TAGS`)
+    pub exclude_tags: Vec<String>,
+    /// List the selected tests instead of running them
+    pub list: bool,
+    /// Print [`USAGE`] instead of running the tests (`-h` / `--help`)
+    pub help: bool,
+    /// Only run the ignored tests (`--ignored`)
+    pub ignored: bool,
+    /// Run the ignored tests along with the others (`--include-ignored`)
+    pub include_ignored: bool,
+    /// Browsers to run the tests in (`--browser` / `E2E_BROWSERS`)
+    pub browsers: Vec<BrowserType>,
+    /// Devices emulated in each browser, none unless set, see [`device`](crate::device)
+    /// (`--device desktop,pixel,iphone,ipad` / `E2E_DEVICES`)
+    pub devices: Vec<Device>,
+    /// Skip browsers that fail to launch instead of failing the whole run
+    /// (`--skip-missing-browsers` / `E2E_SKIP_MISSING_BROWSERS`)
+    pub skip_missing_browsers: bool,
+    /// How long to wait for the web server to be ready (`--server-timeout <seconds>` /
+    /// `E2E_SERVER_TIMEOUT`)
+    pub server_timeout: Option<Duration>,
+    /// Path polled to know when the web server is ready, instead of waiting for its port to
+    /// accept connections (`--server-health-path` / `E2E_SERVER_HEALTH_PATH`)
+    pub server_health_path: Option<String>,
+    /// JSON file describing the server to run the tests against, see
+    /// [`Server::from_file`](crate::server::Server::from_file) (`--server-config` /
+    /// `E2E_SERVER_CONFIG`)
+    pub server_config: Option<PathBuf>,
+    /// Don't launch a server, run the tests against this URL (`--server-url` / `E2E_SERVER_URL`)
+    pub server_url: Option<String>,
+    /// Default timeout of each test, 30 seconds unless set (`--test-timeout <seconds>` /
+    /// `E2E_TEST_TIMEOUT`)
+    pub test_timeout: Duration,
+    /// How many tests run at the same time, the number of CPUs unless set (`--jobs` /
+    /// `--test-threads` / `E2E_JOBS`, `--serial` for 1)
+    pub jobs: usize,
+    /// How many tests run at the same time in each browser, on top of `jobs`
+    /// (`--jobs-per-browser` / `E2E_JOBS_PER_BROWSER`)
+    pub jobs_per_browser: Option<usize>,
+    /// How many times a failed test is retried (`--retries` / `E2E_RETRIES`)
+    pub retries: usize,
+    /// Fail the run when a test only passed after being retried (`--fail-on-flaky` /
+    /// `E2E_FAIL_ON_FLAKY`)
+    pub fail_on_flaky: bool,
+    /// Write a JUnit XML report to this path (`--junit` / `E2E_JUNIT`)
+    pub junit: Option<PathBuf>,
+    /// Write an HTML report to this path (`--html` / `E2E_HTML`)
+    pub html: Option<PathBuf>,
+    /// How progress is printed on stdout (`--format pretty|json` / `E2E_FORMAT`, libtest's `terse`
+    /// is printed as `pretty`). Without the `nightly` feature, what the tests print ends up in
+    /// the middle of the `json` events
+    pub format: Format,
+    /// How many of the slowest tests the summary lists, 5 unless set, 0 for none (`--slowest` /
+    /// `E2E_SLOWEST`)
+    pub slowest: usize,
+    /// Where screenshots and other artifacts are saved, in a subdirectory per run,
+    /// `target/e2e-artifacts` unless set (`--artifacts-dir` / `E2E_ARTIFACTS_DIR`)
+    pub artifacts_dir: PathBuf,
+    /// Whether the pages are recorded, off unless set (`--video off|on|retain-on-failure` /
+    /// `E2E_VIDEO`)
+    pub video: Video,
+    /// Fail tests whose page threw an error it didn't catch (`--fail-on-page-error` /
+    /// `E2E_FAIL_ON_PAGE_ERROR`)
+    pub fail_on_page_error: bool,
+    /// Most verbose `log` and `tracing` records captured with the output of the tests, `info`
+    /// unless set (`--log-level off|error|warn|info|debug|trace` / `E2E_LOG_LEVEL`)
+    pub log_level: log::LevelFilter,
+    /// Tag the requests of the tests with [`TEST_ID_HEADER`](crate::server::TEST_ID_HEADER) when
+    /// the runner launched the server, unless turned off (`--no-test-id-header` /
+    /// `E2E_TEST_ID_HEADER=0`). It is sent to every origin the pages talk to, so cross-origin
+    /// requests need a CORS preflight
+    pub test_id_header: bool,
+    /// Options of the browser contexts the tests run in, which they can override:
+    /// `--viewport 1280x720`, `--locale fr-FR`, `--timezone Europe/Paris`,
+    /// `--geolocation <latitude>,<longitude>[,<accuracy>]`,
+    /// `--color-scheme light|dark|no-preference`, `--user-agent`, `--permissions a,b`,
+    /// `--header 'Name: value'` (repeatable) and `--javascript-disabled`, or the `E2E_VIEWPORT`,
+    /// `E2E_LOCALE`, `E2E_TIMEZONE`, `E2E_GEOLOCATION`, `E2E_COLOR_SCHEME`, `E2E_USER_AGENT`,
+    /// `E2E_PERMISSIONS`, `E2E_HEADERS` (one header per line) and `E2E_JAVASCRIPT_DISABLED`
+    /// environment variables
+    pub context: ContextOptions,
+}
+
+/// Printed by `--help`
+pub const USAGE: &str = "\
+Usage: cargo test [--test <name>] -- [OPTIONS] [FILTERS...]
+
+Runs the tests whose name contains one of the filters, all of them without filters. Most options
+can also be set with `E2E_*` environment variables, see `e2e::options::Options`.
+
+Options:
+    --exact                 Match the filters against the whole test name
+    --skip FILTER           Skip the tests whose name contains FILTER, repeatable
+    --list                  List the tests instead of running them
+    --ignored               Only run the ignored tests
+    --include-ignored       Run the ignored tests as well
+    --tags A,B              Only run the tests with one of these tags
+    --exclude-tags A,B      Skip the tests with one of these tags
+    --browser A,B           Browsers to run the tests in: chromium, firefox, webkit
+    --device A,B            Devices to emulate: desktop, pixel, iphone, ipad
+    --skip-missing-browsers Skip the browsers that fail to launch
+    --server-timeout SECS   How long to wait for the server to be ready
+    --server-health-path P  Path polled to know when the server is ready
+    --server-config FILE    JSON file describing the server to run the tests against
+    --server-url URL        Run the tests against this server instead of launching one
+    --test-timeout SECS     Default timeout of each test, 30 seconds unless set
+    --jobs N                How many tests run at the same time, also --test-threads
+    --jobs-per-browser N    How many tests run at the same time in each browser
+    --serial                Run one test at a time
+    --retries N             How many times a failed test is retried
+    --fail-on-flaky         Fail the run when a test only passed after being retried
+    --junit FILE            Write a JUnit XML report
+    --html FILE             Write an HTML report
+    --format pretty|json    How progress is printed
+    --slowest N             How many of the slowest tests the summary lists
+    --artifacts-dir DIR     Where screenshots and videos are saved
+    --video off|on|retain-on-failure
+                            Whether the pages are recorded
+    --fail-on-page-error    Fail the tests whose page threw an uncaught error
+    --log-level LEVEL       Most verbose log records captured: off, error, ..., trace
+    --no-test-id-header     Don't tag the requests of the tests with x-e2e-test-id
+    --viewport WxH          Size of the pages, e.g. 1280x720
+    --locale LOCALE         e.g. fr-FR
+    --timezone TZ           e.g. Europe/Paris
+    --geolocation LAT,LON[,ACCURACY]
+                            Position reported by the geolocation API
+    --color-scheme light|dark|no-preference
+    --user-agent UA
+    --permissions A,B       Permissions granted to the pages
+    --header 'NAME: VALUE'  Sent with every request of the pages, repeatable
+    --javascript-disabled   Don't run JavaScript in the pages
+    -h, --help              Print this help
+
+The libtest flags that don't apply, e.g. --nocapture or --quiet, are accepted and ignored.
+";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable output
+    Pretty,
+    /// Newline-delimited JSON events, similar to libtest's `--format json`
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = OptionsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" | "terse" => Ok(Format::Pretty),
+            "json" => Ok(Format::Json),
+            _ => Err(OptionsError::InvalidValue("--format", s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Video {
+    Off,
+    /// Keep the video of every test
+    On,
+    /// Record every test but only keep the videos of failed attempts
+    RetainOnFailure,
+}
+
+impl FromStr for Video {
+    type Err = OptionsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Video::Off),
+            "on" => Ok(Video::On),
+            "retain-on-failure" => Ok(Video::RetainOnFailure),
+            _ => Err(OptionsError::InvalidValue("--video", s.to_string())),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            filters: Vec::new(),
+            skip: Vec::new(),
+            exact: false,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            list: false,
+            help: false,
+            ignored: false,
+            include_ignored: false,
+            browsers: BrowserType::ALL.to_vec(),
+            devices: Vec::new(),
+            skip_missing_browsers: false,
+            server_timeout: None,
+            server_health_path: None,
+            server_config: None,
+            server_url: None,
+            test_timeout: Duration::from_secs(30),
+            jobs: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            jobs_per_browser: None,
+            retries: 0,
+            fail_on_flaky: false,
+            junit: None,
+            html: None,
+            format: Format::Pretty,
+            slowest: 5,
+            artifacts_dir: PathBuf::from("target/e2e-artifacts"),
+            video: Video::Off,
+            fail_on_page_error: false,
+            log_level: log::LevelFilter::Info,
+            test_id_header: true,
+            context: ContextOptions::new(),
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Result<Self, OptionsError> {
+        let mut options = Options::default();
+        options.read_env(|key| std::env::var(key).ok())?;
+        options.read_args(std::env::args().skip(1))?;
+        Ok(options)
+    }
+
+    pub fn read_env<F: Fn(&str) -> Option<String>>(&mut self, var: F) -> Result<(), OptionsError> {
+        if let Some(tags) = var("E2E_TAGS") {
+            self.tags = parse_list(&tags);
+        }
+        if let Some(exclude_tags) = var("E2E_EXCLUDE_TAGS") {
+            self.exclude_tags = parse_list(&exclude_tags);
+        }
+        if let Some(browsers) = var("E2E_BROWSERS") {
+            self.browsers = parse_browsers(&browsers)?;
+        }
+        if let Some(devices) = var("E2E_DEVICES") {
+            self.devices = parse_devices(&devices)?;
+        }
+        if let Some(skip_missing_browsers) = var("E2E_SKIP_MISSING_BROWSERS") {
+            self.skip_missing_browsers = parse_bool(&skip_missing_browsers);
+        }
+        if let Some(server_timeout) = var("E2E_SERVER_TIMEOUT") {
+            self.server_timeout = Some(parse_seconds("E2E_SERVER_TIMEOUT", &server_timeout)?);
+        }
+        if let Some(server_health_path) = var("E2E_SERVER_HEALTH_PATH") {
+            self.server_health_path = Some(server_health_path);
+        }
+        if let Some(server_config) = var("E2E_SERVER_CONFIG") {
+            self.server_config = Some(server_config.into());
+        }
+        if let Some(server_url) = var("E2E_SERVER_URL") {
+            self.server_url = Some(server_url);
+        }
+        if let Some(test_timeout) = var("E2E_TEST_TIMEOUT") {
+            self.test_timeout = parse_seconds("E2E_TEST_TIMEOUT", &test_timeout)?;
+        }
+        if let Some(jobs) = var("E2E_JOBS") {
+            self.jobs = parse_jobs("E2E_JOBS", &jobs)?;
+        }
+        if let Some(jobs_per_browser) = var("E2E_JOBS_PER_BROWSER") {
+            self.jobs_per_browser = Some(parse_jobs("E2E_JOBS_PER_BROWSER", &jobs_per_browser)?);
+        }
+        if let Some(retries) = var("E2E_RETRIES") {
+            self.retries = parse_count("E2E_RETRIES", &retries)?;
+        }
+        if let Some(fail_on_flaky) = var("E2E_FAIL_ON_FLAKY") {
+            self.fail_on_flaky = parse_bool(&fail_on_flaky);
+        }
+        if let Some(junit) = var("E2E_JUNIT") {
+            self.junit = Some(junit.into());
+        }
+        if let Some(html) = var("E2E_HTML") {
+            self.html = Some(html.into());
+        }
+        if let Some(format) = var("E2E_FORMAT") {
+            self.format = format.parse()?;
+        }
+        if let Some(slowest) = var("E2E_SLOWEST") {
+            self.slowest = parse_count("E2E_SLOWEST", &slowest)?;
+        }
+        if let Some(artifacts_dir) = var("E2E_ARTIFACTS_DIR") {
+            self.artifacts_dir = artifacts_dir.into();
+        }
+        if let Some(video) = var("E2E_VIDEO") {
+            self.video = video.parse()?;
+        }
+        if let Some(fail_on_page_error) = var("E2E_FAIL_ON_PAGE_ERROR") {
+            self.fail_on_page_error = parse_bool(&fail_on_page_error);
+        }
+        if let Some(log_level) = var("E2E_LOG_LEVEL") {
+            self.log_level = parse_log_level("E2E_LOG_LEVEL", &log_level)?;
+        }
+        if let Some(test_id_header) = var("E2E_TEST_ID_HEADER") {
+            self.test_id_header = parse_bool(&test_id_header);
+        }
+        if let Some(viewport) = var("E2E_VIEWPORT") {
+            self.context.viewport = Some(parse_value("E2E_VIEWPORT", &viewport)?);
+        }
+        if let Some(locale) = var("E2E_LOCALE") {
+            self.context.locale = Some(leak(locale));
+        }
+        if let Some(timezone) = var("E2E_TIMEZONE") {
+            self.context.timezone = Some(leak(timezone));
+        }
+        if let Some(geolocation) = var("E2E_GEOLOCATION") {
+            self.context.geolocation = Some(parse_value("E2E_GEOLOCATION", &geolocation)?);
+        }
+        if let Some(color_scheme) = var("E2E_COLOR_SCHEME") {
+            self.context.color_scheme = Some(
+                parse_color_scheme(&color_scheme)
+                    .ok_or(OptionsError::InvalidValue("E2E_COLOR_SCHEME", color_scheme))?,
+            );
+        }
+        if let Some(user_agent) = var("E2E_USER_AGENT") {
+            self.context.user_agent = Some(leak(user_agent));
+        }
+        if let Some(permissions) = var("E2E_PERMISSIONS") {
+            self.context.permissions = leak_list(parse_list(&permissions).into_iter().map(leak));
+        }
+        if let Some(headers) = var("E2E_HEADERS") {
+            let headers = headers.lines().filter(|header| !header.trim().is_empty());
+            self.context.extra_http_headers = leak_list(
+                headers
+                    .map(|header| parse_header("E2E_HEADERS", header))
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        }
+        if let Some(javascript_disabled) = var("E2E_JAVASCRIPT_DISABLED") {
+            self.context.javascript_enabled = Some(!parse_bool(&javascript_disabled));
+        }
+        Ok(())
+    }
+
+    pub fn read_args<I: IntoIterator<Item = String>>(
+        &mut self,
+        args: I,
+    ) -> Result<(), OptionsError> {
+        let mut args = args.into_iter();
+        // Repeatable flags add up, but replace the value of the environment
+        let mut tags = None;
+        let mut exclude_tags = None;
+        let mut permissions = None;
+        let mut headers = None;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |flag: &'static str| {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(OptionsError::MissingValue(flag))
+            };
+
+            match flag {
+                "--exact" => self.exact = true,
+                "--list" => self.list = true,
+                "-h" | "--help" => self.help = true,
+                "--ignored" => self.ignored = true,
+                "--include-ignored" => self.include_ignored = true,
+                "--skip" => self.skip.push(value("--skip")?),
+                "--tags" => tags
+                    .get_or_insert_with(Vec::new)
+                    .extend(parse_list(&value("--tags")?)),
+                "--exclude-tags" => exclude_tags
+                    .get_or_insert_with(Vec::new)
+                    .extend(parse_list(&value("--exclude-tags")?)),
+                "--browser" => self.browsers = parse_browsers(&value("--browser")?)?,
+                "--device" => self.devices = parse_devices(&value("--device")?)?,
+                "--skip-missing-browsers" => self.skip_missing_browsers = true,
+                "--server-timeout" => {
+                    self.server_timeout = Some(parse_seconds(
+                        "--server-timeout",
+                        &value("--server-timeout")?,
+                    )?)
+                }
+                "--server-health-path" => {
+                    self.server_health_path = Some(value("--server-health-path")?)
+                }
+                "--server-config" => self.server_config = Some(value("--server-config")?.into()),
+                "--server-url" => self.server_url = Some(value("--server-url")?),
+                "--test-timeout" => {
+                    self.test_timeout = parse_seconds("--test-timeout", &value("--test-timeout")?)?
+                }
+                "--jobs" => self.jobs = parse_jobs("--jobs", &value("--jobs")?)?,
+                "--jobs-per-browser" => {
+                    self.jobs_per_browser = Some(parse_jobs(
+                        "--jobs-per-browser",
+                        &value("--jobs-per-browser")?,
+                    )?)
+                }
+                "--test-threads" => {
+                    self.jobs = parse_jobs("--test-threads", &value("--test-threads")?)?
+                }
+                "--serial" => self.jobs = 1,
+                "--retries" => self.retries = parse_count("--retries", &value("--retries")?)?,
+                "--fail-on-flaky" => self.fail_on_flaky = true,
+                "--junit" => self.junit = Some(value("--junit")?.into()),
+                "--html" => self.html = Some(value("--html")?.into()),
+                "--format" => self.format = value("--format")?.parse()?,
+                "--slowest" => self.slowest = parse_count("--slowest", &value("--slowest")?)?,
+                "--artifacts-dir" => self.artifacts_dir = value("--artifacts-dir")?.into(),
+                "--video" => self.video = value("--video")?.parse()?,
+                "--fail-on-page-error" => self.fail_on_page_error = true,
+                "--log-level" => {
+                    self.log_level = parse_log_level("--log-level", &value("--log-level")?)?
+                }
+                "--no-test-id-header" => self.test_id_header = false,
+                "--viewport" => {
+                    self.context.viewport = Some(parse_value("--viewport", &value("--viewport")?)?)
+                }
+                "--locale" => self.context.locale = Some(leak(value("--locale")?)),
+                "--timezone" => self.context.timezone = Some(leak(value("--timezone")?)),
+                "--geolocation" => {
+                    self.context.geolocation =
+                        Some(parse_value("--geolocation", &value("--geolocation")?)?)
+                }
+                "--color-scheme" => {
+                    let color_scheme = value("--color-scheme")?;
+                    self.context.color_scheme = Some(
+                        parse_color_scheme(&color_scheme)
+                            .ok_or(OptionsError::InvalidValue("--color-scheme", color_scheme))?,
+                    );
+                }
+                "--user-agent" => self.context.user_agent = Some(leak(value("--user-agent")?)),
+                "--permissions" => permissions
+                    .get_or_insert_with(Vec::new)
+                    .extend(parse_list(&value("--permissions")?).into_iter().map(leak)),
+                "--header" => headers
+                    .get_or_insert_with(Vec::new)
+                    .push(parse_header("--header", &value("--header")?)?),
+                "--javascript-disabled" => self.context.javascript_enabled = Some(false),
+                // libtest flags that don't apply here
+                "--nocapture" | "--show-output" | "-q" | "--quiet" => {}
+                "--color" => drop(value("--color")?),
+                "-Z" => drop(value("-Z")?),
+                _ if flag.starts_with("-Z") => {}
+                _ if flag.starts_with('-') => return Err(OptionsError::UnknownFlag(arg)),
+                _ => self.filters.push(arg),
+            }
+        }
+
+        if let Some(tags) = tags {
+            self.tags = tags;
+        }
+        if let Some(exclude_tags) = exclude_tags {
+            self.exclude_tags = exclude_tags;
+        }
+        if let Some(permissions) = permissions {
+            self.context.permissions = leak_list(permissions);
+        }
+        if let Some(headers) = headers {
+            self.context.extra_http_headers = leak_list(headers);
+        }
+        Ok(())
+    }
+
+    /// Whether the test named `test_name` is selected by the filters and not skipped
+    pub fn is_selected(&self, test_name: &str) -> bool {
+        let selected = self.filters.is_empty()
+            || self
+                .filters
+                .iter()
+                .any(|filter| self.matches(test_name, filter));
+
+        selected && !self.skip.iter().any(|skip| self.matches(test_name, skip))
+    }
+
+    /// Whether a test with `tags` has one of the selected tags, if any, and none of the excluded
+    /// ones
+    pub fn has_selected_tags(&self, tags: &[&str]) -> bool {
+        let has = |selected: &[String]| selected.iter().any(|tag| tags.contains(&tag.as_str()));
+        (self.tags.is_empty() || has(&self.tags)) && !has(&self.exclude_tags)
+    }
+
+    /// What each test runs on: every browser, as every device if there are some
+    pub fn targets(&self) -> Vec<Target> {
+        let mut targets = Vec::new();
+        for &browser_type in &self.browsers {
+            if self.devices.is_empty() {
+                targets.push(Target {
+                    browser_type,
+                    device: None,
+                });
+            }
+            for &device in self
+                .devices
+                .iter()
+                .filter(|device| device.runs_in(browser_type))
+            {
+                targets.push(Target {
+                    browser_type,
+                    device: Some(device),
+                });
+            }
+        }
+        targets
+    }
+
+    /// The devices a browser can't emulate, which tests don't run as in it
+    pub fn skipped_targets(&self) -> Vec<(BrowserType, Device)> {
+        let mut skipped = Vec::new();
+        for &browser_type in &self.browsers {
+            for &device in &self.devices {
+                if !device.runs_in(browser_type) {
+                    skipped.push((browser_type, device));
+                }
+            }
+        }
+        skipped
+    }
+
+    fn matches(&self, test_name: &str, filter: &str) -> bool {
+        if self.exact {
+            test_name == filter
+        } else {
+            test_name.contains(filter)
+        }
+    }
+}
+
+fn parse_browsers(list: &str) -> Result<Vec<BrowserType>, OptionsError> {
+    let mut browsers = Vec::new();
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let browser_type = BrowserType::from_str(name)?;
+        if !browsers.contains(&browser_type) {
+            browsers.push(browser_type);
+        }
+    }
+    Ok(browsers)
+}
+
+fn parse_devices(list: &str) -> Result<Vec<Device>, OptionsError> {
+    let mut devices = Vec::new();
+    for name in parse_list(list) {
+        let device = Device::find(&name).ok_or(OptionsError::UnknownDevice(name))?;
+        if !devices.contains(&device) {
+            devices.push(device);
+        }
+    }
+    Ok(devices)
+}
+
+fn parse_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_seconds(name: &'static str, value: &str) -> Result<Duration, OptionsError> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| OptionsError::InvalidValue(name, value.to_string()))
+}
+
+fn parse_jobs(name: &'static str, value: &str) -> Result<usize, OptionsError> {
+    match parse_count(name, value)? {
+        0 => Err(OptionsError::InvalidValue(name, value.to_string())),
+        jobs => Ok(jobs),
+    }
+}
+
+fn parse_count(name: &'static str, value: &str) -> Result<usize, OptionsError> {
+    value
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| OptionsError::InvalidValue(name, value.to_string()))
+}
+
+fn parse_log_level(name: &'static str, value: &str) -> Result<log::LevelFilter, OptionsError> {
+    value
+        .parse()
+        .map_err(|_| OptionsError::InvalidValue(name, value.to_string()))
+}
+
+fn parse_value<T: FromStr>(name: &'static str, value: &str) -> Result<T, OptionsError> {
+    value
+        .parse()
+        .map_err(|_| OptionsError::InvalidValue(name, value.to_string()))
+}
+
+/// `Name: value`
+fn parse_header(
+    name: &'static str,
+    header: &str,
+) -> Result<(&'static str, &'static str), OptionsError> {
+    match header.split_once(':') {
+        Some((header_name, value)) if !header_name.trim().is_empty() => Ok((
+            leak(header_name.trim().to_string()),
+            leak(value.trim().to_string()),
+        )),
+        _ => Err(OptionsError::InvalidValue(name, header.to_string())),
+    }
+}
+
+/// The context options are `'static` for tests to declare them in constants, the ones of the run
+/// are parsed once and live as long as it
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+fn leak_list<T, I: IntoIterator<Item = T>>(items: I) -> &'static [T] {
+    Box::leak(items.into_iter().collect::<Vec<_>>().into_boxed_slice())
+}
+
+fn parse_bool(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false" | "no" | "off")
+}
+
+#[derive(Debug)]
+pub enum OptionsError {
+    UnknownFlag(String),
+    MissingValue(&'static str),
+    UnknownBrowser(String),
+    UnknownDevice(String),
+    InvalidValue(&'static str, String),
+}
+
+impl Display for OptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionsError::UnknownFlag(flag) => write!(f, "Unknown flag {}", flag),
+            OptionsError::MissingValue(flag) => write!(f, "Missing value for {}", flag),
+            OptionsError::UnknownBrowser(name) => write!(
+                f,
+                "Unknown browser {}, expected chromium, firefox or webkit",
+                name
+            ),
+            OptionsError::UnknownDevice(name) => write!(
+                f,
+                "Unknown device {}, expected desktop, pixel, iphone or ipad",
+                name
+            ),
+            OptionsError::InvalidValue(name, value) => {
+                write!(f, "Invalid value {:?} for {}", value, name)
+            }
+        }
+    }
+}
+
+impl Error for OptionsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(args: &[&str]) -> Result<Options, OptionsError> {
+        let mut options = Options::default();
+        options.read_args(args.iter().map(|arg| arg.to_string()))?;
+        Ok(options)
+    }
+
+    fn parse_with_env(env: &[(&str, &str)], args: &[&str]) -> Result<Options, OptionsError> {
+        let env: HashMap<&str, &str> = env.iter().copied().collect();
+        let mut options = Options::default();
+        options.read_env(|key| env.get(key).map(|value| value.to_string()))?;
+        options.read_args(args.iter().map(|arg| arg.to_string()))?;
+        Ok(options)
+    }
+
+    #[test]
+    fn filters_and_skip() {
+        let options = parse(&["login", "--skip", "admin", "checkout"]).unwrap();
+        assert!(options.is_selected("tests::login"));
+        assert!(options.is_selected("checkout_as_guest"));
+        assert!(!options.is_selected("login_as_admin"));
+        assert!(!options.is_selected("search"));
+    }
+
+    #[test]
+    fn exact_applies_to_skip() {
+        let options = parse(&["--exact", "--skip", "login", "login", "login_as_admin"]).unwrap();
+        assert!(!options.is_selected("login"));
+        assert!(options.is_selected("login_as_admin"));
+        assert!(!options.is_selected("tests::login_as_admin"));
+    }
+
+    #[test]
+    fn inline_values() {
+        let options = parse(&["--retries=2", "--browser=firefox,webkit", "--format=json"]).unwrap();
+        assert_eq!(options.retries, 2);
+        assert_eq!(
+            options.browsers,
+            [BrowserType::Firefox, BrowserType::Webkit]
+        );
+        assert_eq!(options.format, Format::Json);
+    }
+
+    #[test]
+    fn libtest_flags_are_ignored() {
+        let options = parse(&[
+            "--nocapture",
+            "--show-output",
+            "-q",
+            "--color",
+            "never",
+            "-Z",
+            "unstable-options",
+            "-Zunstable-options",
+            "--format=terse",
+            "--test-threads=3",
+            "login",
+        ])
+        .unwrap();
+        assert_eq!(options.filters, ["login"]);
+        assert_eq!(options.format, Format::Pretty);
+        assert_eq!(options.jobs, 3);
+    }
+
+    #[test]
+    fn help() {
+        assert!(parse(&["--help"]).unwrap().help);
+        assert!(parse(&["-h"]).unwrap().help);
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert!(matches!(
+            parse(&["--no-such-flag"]),
+            Err(OptionsError::UnknownFlag(flag)) if flag == "--no-such-flag"
+        ));
+        assert!(matches!(
+            parse(&["--retries"]),
+            Err(OptionsError::MissingValue("--retries"))
+        ));
+        assert!(matches!(
+            parse(&["--jobs", "0"]),
+            Err(OptionsError::InvalidValue("--jobs", _))
+        ));
+        assert!(matches!(
+            parse(&["--browser", "lynx"]),
+            Err(OptionsError::UnknownBrowser(_))
+        ));
+    }
+
+    #[test]
+    fn seconds() {
+        let options = parse(&["--test-timeout", "1.5"]).unwrap();
+        assert_eq!(options.test_timeout, Duration::from_millis(1500));
+        for seconds in ["-1", "NaN", "inf", "1e30", "soon"] {
+            assert!(
+                matches!(
+                    parse(&["--test-timeout", seconds]),
+                    Err(OptionsError::InvalidValue("--test-timeout", _))
+                ),
+                "{}",
+                seconds
+            );
+        }
+    }
+
+    #[test]
+    fn args_take_precedence_over_env() {
+        let env = [
+            ("E2E_BROWSERS", "webkit"),
+            ("E2E_RETRIES", "1"),
+            ("E2E_TAGS", "smoke"),
+            ("E2E_PERMISSIONS", "geolocation"),
+            ("E2E_HEADERS", "X-From: env"),
+        ];
+        let options = parse_with_env(&env, &[]).unwrap();
+        assert_eq!(options.browsers, [BrowserType::Webkit]);
+        assert_eq!(options.retries, 1);
+        assert_eq!(options.tags, ["smoke"]);
+        assert_eq!(options.context.permissions, ["geolocation"]);
+        assert_eq!(options.context.extra_http_headers, [("X-From", "env")]);
+
+        let options = parse_with_env(
+            &env,
+            &[
+                "--browser",
+                "chromium",
+                "--retries",
+                "2",
+                "--tags",
+                "slow",
+                "--tags",
+                "visual",
+                "--permissions",
+                "clipboard-read",
+                "--header",
+                "X-From: args",
+                "--header",
+                "X-Other: args",
+            ],
+        )
+        .unwrap();
+        assert_eq!(options.browsers, [BrowserType::Chromium]);
+        assert_eq!(options.retries, 2);
+        assert_eq!(options.tags, ["slow", "visual"]);
+        assert_eq!(options.context.permissions, ["clipboard-read"]);
+        assert_eq!(
+            options.context.extra_http_headers,
+            [("X-From", "args"), ("X-Other", "args")]
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let options = parse(&["--tags", "smoke,visual", "--exclude-tags", "slow"]).unwrap();
+        assert!(options.has_selected_tags(&["smoke"]));
+        assert!(options.has_selected_tags(&["visual", "mobile"]));
+        assert!(!options.has_selected_tags(&["smoke", "slow"]));
+        assert!(!options.has_selected_tags(&[]));
+    }
+
+    #[test]
+    fn targets() {
+        let options =
+            parse(&["--browser", "chromium,firefox", "--device", "desktop,pixel"]).unwrap();
+        let targets: Vec<String> = options.targets().iter().map(Target::to_string).collect();
+        assert_eq!(
+            targets,
+            [
+                "Chromium / Desktop",
+                "Chromium / Pixel-like",
+                "Firefox / Desktop"
+            ]
+        );
+        assert_eq!(
+            options.skipped_targets(),
+            [(BrowserType::Firefox, Device::PIXEL)]
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::timeout::PendingGuard;
 use anyhow::Result;
 use async_trait::async_trait;
 use futures::StreamExt;
@@ -13,26 +14,32 @@ use std::{
 
 #[async_trait]
 pub trait PageFetchExt {
-    async fn fetch<S: Serialize + Send>(
-        &self,
-        method: Method,
-        url: &str,
-        body: Option<S>,
-    ) -> Result<Response>;
+    async fn fetch<S>(&self, method: Method, url: &str, body: Option<S>) -> Result<Response>
+    where
+        S: Serialize + Send;
 
     async fn get(&self, url: &str) -> Result<Response> {
         self.fetch::<()>(Method::GET, url, None).await
     }
 
-    async fn post<S: Serialize + Send>(&self, url: &str, body: S) -> Result<Response> {
+    async fn post<S>(&self, url: &str, body: S) -> Result<Response>
+    where
+        S: Serialize + Send,
+    {
         self.fetch(Method::POST, url, Some(body)).await
     }
 
-    async fn put<S: Serialize + Send>(&self, url: &str, body: S) -> Result<Response> {
+    async fn put<S>(&self, url: &str, body: S) -> Result<Response>
+    where
+        S: Serialize + Send,
+    {
         self.fetch(Method::PUT, url, Some(body)).await
     }
 
-    async fn patch<S: Serialize + Send>(&self, url: &str, body: S) -> Result<Response> {
+    async fn patch<S>(&self, url: &str, body: S) -> Result<Response>
+    where
+        S: Serialize + Send,
+    {
         self.fetch(Method::PATCH, url, Some(body)).await
     }
 
@@ -43,12 +50,10 @@ pub trait PageFetchExt {
 
 #[async_trait]
 impl PageFetchExt for Page {
-    async fn fetch<S: Serialize + Send>(
-        &self,
-        method: Method,
-        url: &str,
-        body: Option<S>,
-    ) -> Result<Response> {
+    async fn fetch<S>(&self, method: Method, url: &str, body: Option<S>) -> Result<Response>
+    where
+        S: Serialize + Send,
+    {
         let e2e_fetch_id = self
             .eval::<u32>(r#"() => window.e2eFetchId = (window.e2eFetchId ?? 0) + 1"#)
             .await?;
@@ -79,6 +84,7 @@ impl PageFetchExt for Page {
         )
         .await?;
 
+        let _pending = PendingGuard::enter(format!("fetch {} {}", method.as_str(), url));
         Ok(response_stream.next().await.ok_or(NotFound)??)
     }
 }
@@ -0,0 +1,148 @@
+//! Tests declared with [`#[e2e::test]`](crate::test), collected without
+//! `custom_test_frameworks` so they also run on stable Rust, from a `harness = false` test
+//! calling [`e2e_main`](crate::e2e_main).
+//!
+//! Hooks declared with the [`hook`](crate::hook) attributes are collected the same way.
+//!
+//! Each test registers itself before `main` runs, from a function the platform calls at load
+//! time, the way the `inventory` crate does. Only the platforms with such a mechanism are
+//! supported, the crate doesn't build on the others rather than silently running no tests.
+
+use crate::{hook::Hook, Testable};
+use std::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+    target_os = "illumos",
+    target_os = "macos",
+    target_os = "ios",
+    windows,
+)))]
+compile_error!("registering tests at load time is not supported on this platform");
+
+static TESTS: AtomicPtr<Registration<&'static dyn Testable>> = AtomicPtr::new(ptr::null_mut());
+static HOOKS: AtomicPtr<Registration<Hook>> = AtomicPtr::new(ptr::null_mut());
+
+/// What can be registered, with the head of its list
+#[doc(hidden)]
+pub trait Registered: Sync + Sized + 'static {
+    fn head() -> &'static AtomicPtr<Registration<Self>>;
+}
+
+impl Registered for &'static dyn Testable {
+    fn head() -> &'static AtomicPtr<Registration<Self>> {
+        &TESTS
+    }
+}
+
+impl Registered for Hook {
+    fn head() -> &'static AtomicPtr<Registration<Self>> {
+        &HOOKS
+    }
+}
+
+/// A node of a list of registered items, only meant to be created by [`register!`]
+#[doc(hidden)]
+pub struct Registration<T: 'static> {
+    pub item: T,
+    pub next: AtomicPtr<Registration<T>>,
+}
+
+impl<T: Registered> Registration<T> {
+    pub fn register(&'static self) {
+        let head = T::head();
+        let mut current = head.load(Ordering::Acquire);
+        loop {
+            self.next.store(current, Ordering::Relaxed);
+            let node = self as *const Registration<T> as *mut Registration<T>;
+            match head.compare_exchange_weak(current, node, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return,
+                Err(head) => current = head,
+            }
+        }
+    }
+}
+
+fn items<T: Registered>() -> Vec<&'static T> {
+    let mut items = Vec::new();
+    let mut node = T::head().load(Ordering::Acquire);
+    while !node.is_null() {
+        // SAFETY: nodes are only ever created from `&'static Registration`s and never removed
+        let registration = unsafe { &*node };
+        items.push(&registration.item);
+        node = registration.next.load(Ordering::Acquire);
+    }
+    items
+}
+
+/// The registered tests, sorted by name as their registration order is up to the linker
+pub(crate) fn registered() -> Vec<&'static dyn Testable> {
+    let mut tests: Vec<_> = items::<&'static dyn Testable>()
+        .into_iter()
+        .copied()
+        .collect();
+    tests.sort_by_key(|test| test.name());
+    tests
+}
+
+/// The registered hooks, sorted by name
+pub(crate) fn hooks() -> Vec<&'static Hook> {
+    let mut hooks = items::<Hook>();
+    hooks.sort_by_key(|hook| hook.name());
+    hooks
+}
+
+/// Registers a `&'static dyn Testable`, or a `Hook` after `hook`, when the binary is loaded,
+/// used by `#[e2e::test]` and the hook attributes
+#[doc(hidden)]
+#[macro_export]
+macro_rules! register {
+    (hook $hook:expr) => {
+        $crate::register!(@ $crate::hook::Hook, $hook);
+    };
+    (@ $type:ty, $item:expr) => {
+        const _: () = {
+            static REGISTRATION: $crate::registry::Registration<$type> =
+                $crate::registry::Registration {
+                    item: $item,
+                    next: ::std::sync::atomic::AtomicPtr::new(::std::ptr::null_mut()),
+                };
+
+            #[used]
+            #[cfg_attr(
+                any(
+                    target_os = "linux",
+                    target_os = "android",
+                    target_os = "freebsd",
+                    target_os = "netbsd",
+                    target_os = "openbsd",
+                    target_os = "dragonfly",
+                    target_os = "illumos",
+                ),
+                link_section = ".init_array"
+            )]
+            #[cfg_attr(
+                any(target_os = "macos", target_os = "ios"),
+                link_section = "__DATA,__mod_init_func"
+            )]
+            #[cfg_attr(windows, link_section = ".CRT$XCU")]
+            static REGISTER: extern "C" fn() = {
+                extern "C" fn register() {
+                    REGISTRATION.register();
+                }
+                register
+            };
+        };
+    };
+    ($test:expr) => {
+        $crate::register!(@ &'static dyn $crate::Testable, $test);
+    };
+}
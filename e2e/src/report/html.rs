@@ -0,0 +1,360 @@
+//! Self-contained HTML report: a test × browser (and device) matrix linking to the details of
+//! every attempt, with the error chain, the captured output and the artifacts saved while it ran.
+
+use crate::{
+    console::PageMessage,
+    device::Target,
+    report::{escape, timestamp},
+    result::{Attempt, TestOutcome, TestResult, TestStatus},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+td a { text-decoration: none; color: inherit; display: block; }
+.passed { background: #d7f5dd; }
+.flaky { background: #fff1c2; }
+.failed { background: #f9d3d3; }
+.expected-failure { background: #e6dcf5; }
+.ignored { background: #f3f3f3; color: #777; }
+.missing { background: #eee; }
+section { margin: 1.5em 0; padding: 0.5em 1em; border-left: 4px solid #ccc; }
+section.failed { border-color: #d33; background: none; }
+section.flaky { border-color: #db0; background: none; }
+section.passed { border-color: #3a3; background: none; }
+section.expected-failure { border-color: #86c; background: none; }
+section.ignored { border-color: #aaa; background: none; color: inherit; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; white-space: pre-wrap; }
+img, video { max-width: 600px; border: 1px solid #ccc; display: block; margin: 0.5em 0; }
+pre .error { color: #b00; }
+ol.chain li:first-child { font-weight: bold; }
+"#;
+
+pub fn write_report(
+    path: &Path,
+    targets: &[Target],
+    results: &[TestResult],
+    duration: Duration,
+) -> io::Result<()> {
+    let base_dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            fs::create_dir_all(parent)?;
+            parent.to_path_buf()
+        }
+        _ => PathBuf::from("."),
+    };
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    file.write_all(render(&base_dir, targets, results, duration).as_bytes())?;
+    file.flush()
+}
+
+fn render(
+    base_dir: &Path,
+    targets: &[Target],
+    results: &[TestResult],
+    duration: Duration,
+) -> String {
+    // Sorted by test name, then in the order the browsers and devices were given
+    let mut by_test: BTreeMap<&str, Vec<&TestResult>> = BTreeMap::new();
+    for result in results {
+        by_test.entry(result.test_name).or_default().push(result);
+    }
+
+    let count = |status| {
+        results
+            .iter()
+            .filter(|result| result.status() == status)
+            .count()
+    };
+
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>E2E test report</title>\n<style>{}</style>\n</head>\n<body>",
+        STYLE
+    )
+    .unwrap();
+    writeln!(
+        html,
+        "<h1>E2E test report</h1>\n<p>{} passed, {} flaky, {} failed, {} expected failures, \
+         {} ignored in {:.1}s{}</p>",
+        count(TestStatus::Passed),
+        count(TestStatus::Flaky),
+        count(TestStatus::Failed),
+        count(TestStatus::ExpectedFailure),
+        count(TestStatus::Ignored),
+        duration.as_secs_f64(),
+        results
+            .iter()
+            .filter_map(TestResult::started_at)
+            .min()
+            .map(|started_at| format!(", started {}", timestamp(started_at)))
+            .unwrap_or_default()
+    )
+    .unwrap();
+
+    html.push_str("<table>\n<tr><th>Test</th>");
+    for &target in targets {
+        let target_duration: Duration = results
+            .iter()
+            .filter(|result| result.target() == target)
+            .map(TestResult::duration)
+            .sum();
+        write!(
+            html,
+            "<th>{} ({:.1}s)</th>",
+            escape(&target.to_string()),
+            target_duration.as_secs_f64()
+        )
+        .unwrap();
+    }
+    html.push_str("</tr>\n");
+    for (test_name, test_results) in &by_test {
+        write!(html, "<tr><td>{}</td>", escape(test_name)).unwrap();
+        for &target in targets {
+            match test_results.iter().find(|result| result.target() == target) {
+                Some(result) => write!(
+                    html,
+                    "<td class=\"{}\"><a href=\"#{}\">{} ({:.1}s)</a></td>",
+                    status_class(result.status()),
+                    anchor(result),
+                    status_label(result),
+                    result.duration().as_secs_f64()
+                )
+                .unwrap(),
+                None => html.push_str("<td class=\"missing\">-</td>"),
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+
+    for test_results in by_test.values() {
+        for &target in targets {
+            if let Some(result) = test_results.iter().find(|result| result.target() == target) {
+                render_result(&mut html, base_dir, result);
+            }
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_result(html: &mut String, base_dir: &Path, result: &TestResult) {
+    let status = result.status();
+    writeln!(
+        html,
+        "<section id=\"{}\" class=\"{}\">\n<h2>{} in {}: {}</h2>",
+        anchor(result),
+        status_class(status),
+        escape(result.test_name),
+        escape(&result.target().to_string()),
+        status_label(result)
+    )
+    .unwrap();
+
+    if let Some(reason) = result.ignored.filter(|reason| !reason.is_empty()) {
+        writeln!(html, "<p>{}</p>", escape(reason)).unwrap();
+    }
+
+    for (index, attempt) in result.attempts.iter().enumerate() {
+        // Only the failing attempts are expanded by default
+        writeln!(
+            html,
+            "<details{}>\n<summary>Attempt {}/{}: {} ({:.2}s, started {})</summary>",
+            if attempt.outcome.is_success() {
+                ""
+            } else {
+                " open"
+            },
+            index + 1,
+            result.attempts.len(),
+            outcome_label(&attempt.outcome),
+            attempt.duration.as_secs_f64(),
+            timestamp(attempt.started_at)
+        )
+        .unwrap();
+        render_attempt(html, base_dir, attempt);
+        html.push_str("</details>\n");
+    }
+
+    html.push_str("</section>\n");
+}
+
+fn render_attempt(html: &mut String, base_dir: &Path, attempt: &Attempt) {
+    match &attempt.outcome {
+        TestOutcome::Passed => {}
+        TestOutcome::Failed(err) => {
+            html.push_str("<ol class=\"chain\">\n");
+            for cause in err.chain() {
+                writeln!(html, "<li><pre>{}</pre></li>", escape(&cause.to_string())).unwrap();
+            }
+            html.push_str("</ol>\n");
+        }
+        TestOutcome::TimedOut(timed_out) => {
+            writeln!(html, "<pre>{}</pre>", escape(&timed_out.to_string())).unwrap();
+        }
+    }
+
+    if !attempt.output.is_empty() {
+        writeln!(
+            html,
+            "<details open>\n<summary>Test output</summary>\n<pre>{}</pre>\n</details>",
+            escape(&String::from_utf8_lossy(&attempt.output))
+        )
+        .unwrap();
+    }
+
+    if !attempt.page_messages.is_empty() {
+        html.push_str("<details open>\n<summary>Browser console</summary>\n<pre>");
+        for message in &attempt.page_messages {
+            let class = match message {
+                PageMessage::Error(_) => " class=\"error\"",
+                PageMessage::Console { kind, .. } if kind == "error" => " class=\"error\"",
+                PageMessage::Console { .. } => "",
+            };
+            writeln!(
+                html,
+                "<span{}>{}</span>",
+                class,
+                escape(&message.to_string())
+            )
+            .unwrap();
+        }
+        html.push_str("</pre>\n</details>\n");
+    }
+
+    if !attempt.server_log.is_empty() {
+        writeln!(
+            html,
+            "<details open>\n<summary>Server log</summary>\n<pre>{}</pre>\n</details>",
+            escape(&attempt.server_log.join("\n"))
+        )
+        .unwrap();
+    }
+
+    if !attempt.artifacts.is_empty() {
+        html.push_str("<ul>\n");
+        for artifact in &attempt.artifacts {
+            let link = escape(&relative_link(base_dir, artifact));
+            let name = artifact
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            write!(html, "<li><a href=\"{}\">{}</a>", link, escape(&name)).unwrap();
+            match artifact.extension().and_then(|ext| ext.to_str()) {
+                Some("png") => write!(html, "<a href=\"{0}\"><img src=\"{0}\"></a>", link).unwrap(),
+                Some("webm") => write!(html, "<video src=\"{}\" controls></video>", link).unwrap(),
+                _ => {}
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n");
+    }
+}
+
+/// Link to `artifact` from a page in `base_dir`, relative when the artifact is below it
+fn relative_link(base_dir: &Path, artifact: &Path) -> String {
+    let absolute = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let (base_dir, artifact) = (absolute(base_dir), absolute(artifact));
+    match artifact.strip_prefix(&base_dir) {
+        Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+        Err(_) => format!("file://{}", artifact.to_string_lossy().replace('\\', "/")),
+    }
+}
+
+fn anchor(result: &TestResult) -> String {
+    let name: String = result
+        .test_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    match result.device {
+        Some(device) => format!("{}-{}-{}", name, result.browser_type, device),
+        None => format!("{}-{}", name, result.browser_type),
+    }
+}
+
+fn status_class(status: TestStatus) -> &'static str {
+    match status {
+        TestStatus::Passed => "passed",
+        TestStatus::Flaky => "flaky",
+        TestStatus::Failed => "failed",
+        TestStatus::ExpectedFailure => "expected-failure",
+        TestStatus::Ignored => "ignored",
+    }
+}
+
+fn status_label(result: &TestResult) -> &'static str {
+    match result.status() {
+        TestStatus::Passed => "passed",
+        TestStatus::Flaky => "flaky",
+        TestStatus::ExpectedFailure => "expected failure",
+        TestStatus::Ignored => "ignored",
+        TestStatus::Failed => match result.attempts.last() {
+            Some(attempt) => outcome_label(&attempt.outcome),
+            None => "failed",
+        },
+    }
+}
+
+fn outcome_label(outcome: &TestOutcome) -> &'static str {
+    match outcome {
+        TestOutcome::Passed => "passed",
+        TestOutcome::Failed(_) => "failed",
+        TestOutcome::TimedOut(_) => "timed out",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{device::Device, report::tests::result, BrowserType};
+
+    #[test]
+    fn anchors() {
+        let mut result = result("fetch::get_404", vec![TestOutcome::Passed]);
+        assert_eq!(anchor(&result), "fetch--get-404-Chromium");
+        result.device = Some(Device::PIXEL);
+        assert_eq!(anchor(&result), "fetch--get-404-Chromium-Pixel-like");
+    }
+
+    #[test]
+    fn renders_matrix_and_details() {
+        let target = Target {
+            browser_type: BrowserType::Chromium,
+            device: None,
+        };
+        let results = [
+            result("<script>", vec![TestOutcome::Passed]),
+            result("fails", vec![TestOutcome::Failed(anyhow::anyhow!("a & b"))]),
+        ];
+        let html = render(
+            Path::new("target"),
+            &[target],
+            &results,
+            Duration::from_secs(3),
+        );
+
+        assert!(
+            html.contains("1 passed, 0 flaky, 1 failed, 0 expected failures, 0 ignored in 3.0s")
+        );
+        assert!(html.contains("<th>Chromium (3.0s)</th>"));
+        assert!(html.contains("<td>&lt;script&gt;</td>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<a href=\"#fails-Chromium\">"));
+        assert!(html.contains("<section id=\"fails-Chromium\" class=\"failed\">"));
+        assert!(html.contains("a &amp; b"));
+    }
+}
@@ -0,0 +1,179 @@
+//! Newline-delimited JSON events on stdout, in the spirit of libtest's `--format json`:
+//!
+//! ```text
+//! { "type": "suite", "event": "started", "test_count": 2, "browsers": ["Chromium"] }
+//! { "type": "test", "event": "started", "name": "hello_world", "browser": "Chromium",
+//!   "device": null }
+//! { "type": "test", "event": "finished", "name": "hello_world", "browser": "Chromium",
+//!   "device": null, "status": "passed", "duration": 0.412,
+//!   "started_at": "2021-07-14T09:30:00.250Z", "finished_at": "2021-07-14T09:30:00.662Z",
+//!   "attempts": [...] }
+//! { "type": "suite", "event": "finished", "passed": 2, "flaky": 0, "failed": 0,
+//!   "expected_failures": 0, "ignored": 0, "filtered_out": 0, "duration": 1.205,
+//!   "browser_durations": { "Chromium": 0.824 } }
+//! ```
+//!
+//! Each event is a single line, they are only wrapped here for readability.
+
+use crate::{
+    console::PageMessage,
+    device::{Device, Target},
+    report::{timestamp, Reporter, Summary},
+    result::{Attempt, TestOutcome, TestResult, TestStatus},
+    BrowserType,
+};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+pub(crate) struct JsonReporter;
+
+impl JsonReporter {
+    fn emit(&self, event: Value) {
+        let mut stdout = io::stdout().lock();
+        // Nothing sensible to do if stdout is gone
+        let _ = writeln!(stdout, "{}", event).and_then(|()| stdout.flush());
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn suite_started(&mut self, test_count: usize, browsers: &[BrowserType]) {
+        self.emit(json!({
+            "type": "suite",
+            "event": "started",
+            "test_count": test_count,
+            "browsers": browsers.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        }));
+    }
+
+    fn browser_skipped(&mut self, browser_type: BrowserType, error: &anyhow::Error) {
+        self.emit(json!({
+            "type": "browser",
+            "event": "skipped",
+            "browser": browser_type.to_string(),
+            "message": format!("{:#}", error),
+        }));
+    }
+
+    fn device_skipped(&mut self, browser_type: BrowserType, device: Device) {
+        self.emit(json!({
+            "type": "device",
+            "event": "skipped",
+            "browser": browser_type.to_string(),
+            "device": device.name,
+        }));
+    }
+
+    fn test_started(&mut self, test_name: &str, target: Target) {
+        self.emit(json!({
+            "type": "test",
+            "event": "started",
+            "name": test_name,
+            "browser": target.browser_type.to_string(),
+            "device": target.device.map(|device| device.name),
+        }));
+    }
+
+    fn test_finished(&mut self, result: &TestResult) {
+        self.emit(json!({
+            "type": "test",
+            "event": "finished",
+            "name": result.test_name,
+            "browser": result.browser_type.to_string(),
+            "device": result.device.map(|device| device.name),
+            "status": status_name(result.status()),
+            "ignore_reason": result.ignored,
+            "duration": result.duration().as_secs_f64(),
+            "started_at": result.started_at().map(timestamp),
+            "finished_at": result.finished_at().map(timestamp),
+            "attempts": result.attempts.iter().map(attempt).collect::<Vec<_>>(),
+        }));
+    }
+
+    fn suite_finished(&mut self, summary: &Summary) {
+        let count = |status| {
+            summary
+                .results
+                .iter()
+                .filter(|result| result.status() == status)
+                .count()
+        };
+        self.emit(json!({
+            "type": "suite",
+            "event": "finished",
+            "passed": count(TestStatus::Passed),
+            "flaky": count(TestStatus::Flaky),
+            "failed": count(TestStatus::Failed),
+            "expected_failures": count(TestStatus::ExpectedFailure),
+            "ignored": count(TestStatus::Ignored),
+            "filtered_out": summary.filtered_out,
+            "duration": summary.duration.as_secs_f64(),
+            "browser_durations": summary
+                .browser_durations()
+                .into_iter()
+                .map(|(browser_type, duration)| {
+                    (browser_type.to_string(), Value::from(duration.as_secs_f64()))
+                })
+                .collect::<serde_json::Map<_, _>>(),
+        }));
+    }
+
+    fn error(&mut self, error: &anyhow::Error) {
+        self.emit(json!({
+            "type": "suite",
+            "event": "error",
+            "message": format!("{:#}", error),
+        }));
+    }
+}
+
+fn attempt(attempt: &Attempt) -> Value {
+    let (outcome, message) = match &attempt.outcome {
+        TestOutcome::Passed => ("passed", None),
+        TestOutcome::Failed(err) => ("failed", Some(format!("{:?}", err))),
+        TestOutcome::TimedOut(timed_out) => ("timed_out", Some(timed_out.to_string())),
+    };
+    json!({
+        "outcome": outcome,
+        "duration": attempt.duration.as_secs_f64(),
+        "started_at": timestamp(attempt.started_at),
+        "finished_at": timestamp(attempt.finished_at()),
+        "message": message,
+        "output": String::from_utf8_lossy(&attempt.output),
+        "page_messages": attempt.page_messages.iter().map(page_message).collect::<Vec<_>>(),
+        "server_log": attempt.server_log,
+        "artifacts": attempt
+            .artifacts
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn page_message(message: &PageMessage) -> Value {
+    match message {
+        PageMessage::Console {
+            kind,
+            text,
+            location,
+        } => json!({
+            "type": "console",
+            "kind": kind,
+            "text": text,
+            "location": location,
+        }),
+        PageMessage::Error(error) => json!({
+            "type": "error",
+            "text": error,
+        }),
+    }
+}
+
+fn status_name(status: TestStatus) -> &'static str {
+    match status {
+        TestStatus::Passed => "passed",
+        TestStatus::Flaky => "flaky",
+        TestStatus::Failed => "failed",
+        TestStatus::ExpectedFailure => "expected_failure",
+        TestStatus::Ignored => "ignored",
+    }
+}
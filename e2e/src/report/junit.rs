@@ -0,0 +1,210 @@
+//! JUnit XML report, in the flavour of Maven Surefire which most CI dashboards understand:
+//! one `<testsuite>` per browser, or per browser and device, one `<testcase>` per test, and
+//! retried attempts reported as `<flakyFailure>` or `<rerunFailure>`.
+
+use crate::{
+    device::Target,
+    report::{escape, timestamp},
+    result::{Attempt, TestOutcome, TestResult, TestStatus},
+};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+pub fn write_report(
+    path: &Path,
+    targets: &[Target],
+    results: &[TestResult],
+    duration: Duration,
+) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    file.write_all(render(targets, results, duration).as_bytes())?;
+    file.flush()
+}
+
+fn render(targets: &[Target], results: &[TestResult], duration: Duration) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        r#"<testsuites name="e2e" tests="{}" failures="{}" time="{:.3}">"#,
+        results.len(),
+        count_failures(results.iter()),
+        duration.as_secs_f64()
+    )
+    .unwrap();
+
+    for &target in targets {
+        let suite: Vec<&TestResult> = results
+            .iter()
+            .filter(|result| result.target() == target)
+            .collect();
+        if suite.is_empty() {
+            continue;
+        }
+
+        let started_at = suite.iter().filter_map(|result| result.started_at()).min();
+        writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="0" skipped="{}" time="{:.3}"{}>"#,
+            escape(&target.to_string()),
+            suite.len(),
+            count_failures(suite.iter().copied()),
+            suite
+                .iter()
+                .filter(|result| result.status() == TestStatus::Ignored)
+                .count(),
+            suite
+                .iter()
+                .map(|result| result.duration())
+                .sum::<Duration>()
+                .as_secs_f64(),
+            started_at
+                .map(|started_at| format!(r#" timestamp="{}""#, timestamp(started_at)))
+                .unwrap_or_default()
+        )
+        .unwrap();
+        for result in suite {
+            render_test_case(&mut xml, result);
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn render_test_case(xml: &mut String, result: &TestResult) {
+    writeln!(
+        xml,
+        r#"    <testcase name="{}" classname="{}" time="{:.3}">"#,
+        escape(result.test_name),
+        escape(&result.target().to_string()),
+        result.duration().as_secs_f64()
+    )
+    .unwrap();
+
+    let status = result.status();
+    if let Some(reason) = result.ignored {
+        writeln!(xml, r#"      <skipped message="{}"/>"#, escape(reason)).unwrap();
+    }
+    let last = result.attempts.len().saturating_sub(1);
+    for (index, attempt) in result.attempts.iter().enumerate() {
+        let element = match (status, index == last) {
+            // Known failures are reported as passing, their output still tells why they fail
+            (TestStatus::Passed, _)
+            | (TestStatus::Flaky, true)
+            | (TestStatus::ExpectedFailure, _) => continue,
+            (TestStatus::Ignored, _) => unreachable!("ignored tests have no attempts"),
+            (TestStatus::Flaky, false) => "flakyFailure",
+            (TestStatus::Failed, true) => "failure",
+            (TestStatus::Failed, false) => "rerunFailure",
+        };
+        render_failure(xml, element, attempt);
+    }
+
+    let mut output = String::new();
+    for attempt in &result.attempts {
+        output.push_str(&String::from_utf8_lossy(&attempt.output));
+    }
+    // The syntax of the Jenkins JUnit attachments plugin, also understood by GitLab
+    for artifact in result
+        .attempts
+        .iter()
+        .flat_map(|attempt| &attempt.artifacts)
+    {
+        writeln!(output, "[[ATTACHMENT|{}]]", artifact.display()).unwrap();
+    }
+    if !output.is_empty() {
+        writeln!(xml, "      <system-out>{}</system-out>", escape(&output)).unwrap();
+    }
+
+    let mut errors = String::new();
+    for attempt in &result.attempts {
+        for message in &attempt.page_messages {
+            writeln!(errors, "{}", message).unwrap();
+        }
+        for line in &attempt.server_log {
+            writeln!(errors, "[server] {}", line).unwrap();
+        }
+    }
+    if !errors.is_empty() {
+        writeln!(xml, "      <system-err>{}</system-err>", escape(&errors)).unwrap();
+    }
+
+    xml.push_str("    </testcase>\n");
+}
+
+fn render_failure(xml: &mut String, element: &str, attempt: &Attempt) {
+    let (kind, message, details) = match &attempt.outcome {
+        TestOutcome::Passed => return,
+        TestOutcome::Failed(err) => ("failed", err.to_string(), format!("{:?}", err)),
+        TestOutcome::TimedOut(timed_out) => {
+            ("timeout", timed_out.to_string(), timed_out.to_string())
+        }
+    };
+    let message = message.lines().next().unwrap_or_default();
+
+    writeln!(
+        xml,
+        r#"      <{element} message="{}" type="{}">{}</{element}>"#,
+        escape(message),
+        kind,
+        escape(&details),
+        element = element
+    )
+    .unwrap();
+}
+
+fn count_failures<'a, I: Iterator<Item = &'a TestResult>>(results: I) -> usize {
+    results
+        .filter(|result| result.status() == TestStatus::Failed)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{report::tests::result, BrowserType};
+
+    #[test]
+    fn renders_suites_and_retries() {
+        let target = Target {
+            browser_type: BrowserType::Chromium,
+            device: None,
+        };
+        let results = [
+            result("passes", vec![TestOutcome::Passed]),
+            result(
+                "flaky",
+                vec![
+                    TestOutcome::Failed(anyhow::anyhow!("expected <b>")),
+                    TestOutcome::Passed,
+                ],
+            ),
+            result(
+                "fails",
+                vec![
+                    TestOutcome::Failed(anyhow::anyhow!("first")),
+                    TestOutcome::Failed(anyhow::anyhow!("second\nline")),
+                ],
+            ),
+        ];
+        let xml = render(&[target], &results, Duration::from_secs(9));
+
+        assert!(xml.contains(r#"<testsuites name="e2e" tests="3" failures="1" time="9.000">"#));
+        assert!(xml.contains(r#"<testsuite name="Chromium" tests="3" failures="1" errors="0" skipped="0" time="7.500" timestamp="2020-09-13T12:26:40.000Z">"#));
+        assert!(xml.contains(r#"<testcase name="passes" classname="Chromium" time="1.500">"#));
+        assert!(xml.contains(r#"<flakyFailure message="expected &lt;b&gt;" type="failed">"#));
+        assert!(xml.contains(r#"<rerunFailure message="first" type="failed">"#));
+        assert!(xml.contains(r#"<failure message="second" type="failed">"#));
+        assert_eq!(xml.matches("<testcase ").count(), 3);
+    }
+}
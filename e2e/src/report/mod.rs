@@ -0,0 +1,196 @@
+use crate::{
+    device::{Device, Target},
+    result::TestResult,
+    BrowserType,
+};
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter},
+    io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+pub mod html;
+pub mod json;
+pub mod junit;
+pub mod pretty;
+
+/// Receives the progress of a run as it happens
+pub(crate) trait Reporter: Send {
+    fn suite_started(&mut self, _test_count: usize, _browsers: &[BrowserType]) {}
+
+    fn browser_skipped(&mut self, _browser_type: BrowserType, _error: &anyhow::Error) {}
+
+    /// The browser can't emulate the device, tests don't run as it there
+    fn device_skipped(&mut self, _browser_type: BrowserType, _device: Device) {}
+
+    fn test_started(&mut self, _test_name: &str, _target: Target) {}
+
+    fn test_finished(&mut self, _result: &TestResult) {}
+
+    fn suite_finished(&mut self, _summary: &Summary) {}
+
+    /// The run could not complete
+    fn error(&mut self, _error: &anyhow::Error) {}
+}
+
+pub(crate) struct Summary<'a> {
+    pub results: &'a [TestResult],
+    pub filtered_out: usize,
+    pub duration: Duration,
+}
+
+impl Summary<'_> {
+    /// Time spent running tests in each browser, in the order of [`BrowserType::ALL`]
+    pub fn browser_durations(&self) -> Vec<(BrowserType, Duration)> {
+        BrowserType::ALL
+            .iter()
+            .filter_map(|&browser_type| {
+                let mut results = self
+                    .results
+                    .iter()
+                    .filter(|result| result.browser_type == browser_type)
+                    .peekable();
+                results.peek()?;
+                Some((browser_type, results.map(TestResult::duration).sum()))
+            })
+            .collect()
+    }
+}
+
+/// Formats `time` as an ISO 8601 UTC timestamp with milliseconds, e.g. `2021-07-14T09:30:00.250Z`
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Escapes text for both attributes and elements of XML or HTML, dropping the characters XML 1.0
+/// forbids
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub struct FailedToWriteReport {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl Display for FailedToWriteReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to write report to {}: {}",
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+impl Debug for FailedToWriteReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for FailedToWriteReport {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::result::{Attempt, TestOutcome};
+
+    /// A result of `test_name` in Chromium, with an attempt per outcome
+    pub(in crate::report) fn result(
+        test_name: &'static str,
+        outcomes: Vec<TestOutcome>,
+    ) -> TestResult {
+        let attempts = outcomes
+            .into_iter()
+            .map(|outcome| Attempt {
+                outcome,
+                output: Vec::new(),
+                page_messages: Vec::new(),
+                server_log: Vec::new(),
+                started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+                duration: Duration::from_millis(1500),
+                artifacts: Vec::new(),
+            })
+            .collect();
+        TestResult {
+            test_name,
+            browser_type: BrowserType::Chromium,
+            device: None,
+            ignored: None,
+            expected_failure: false,
+            attempts,
+        }
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(
+            timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+            "2020-09-13T12:26:40.000Z"
+        );
+    }
+
+    #[test]
+    fn escapes_markup_and_control_characters() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        assert_eq!(
+            escape("\u{1b}[31mred\u{1b}[0m\tok\r\n"),
+            "[31mred[0m\tok\r\n"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    device::Device,
+    report::{Reporter, Summary},
+    result::{TestResult, TestStatus},
+    BrowserType,
+};
+
+/// Human readable output, the default
+pub(crate) struct PrettyReporter {
+    /// How many of the slowest tests the summary lists
+    pub slowest: usize,
+}
+
+impl Reporter for PrettyReporter {
+    fn browser_skipped(&mut self, browser_type: BrowserType, error: &anyhow::Error) {
+        println!("Skipping {}: {:#}", browser_type, error);
+    }
+
+    fn device_skipped(&mut self, browser_type: BrowserType, device: Device) {
+        println!(
+            "Skipping {} in {}: it can't be emulated there",
+            device, browser_type
+        );
+    }
+
+    fn test_finished(&mut self, result: &TestResult) {
+        println!("{}", result);
+    }
+
+    fn suite_finished(&mut self, summary: &Summary) {
+        println!("\nSummary:");
+
+        for test_result in summary.results {
+            println!("{}", test_result);
+        }
+
+        if self.slowest > 0 && !summary.results.is_empty() {
+            let mut slowest: Vec<&TestResult> = summary
+                .results
+                .iter()
+                .filter(|test_result| test_result.status() != TestStatus::Ignored)
+                .collect();
+            slowest.sort_by_key(|test_result| std::cmp::Reverse(test_result.duration()));
+            println!("\nSlowest tests:");
+            for test_result in slowest.into_iter().take(self.slowest) {
+                println!(
+                    "{:>8.2}s  {} in {}",
+                    test_result.duration().as_secs_f64(),
+                    test_result.test_name,
+                    test_result.target()
+                );
+            }
+        }
+
+        println!("\nTime per browser:");
+        for (browser_type, duration) in summary.browser_durations() {
+            println!("{:>8.2}s  {}", duration.as_secs_f64(), browser_type);
+        }
+
+        let count = |status| {
+            summary
+                .results
+                .iter()
+                .filter(|test_result| test_result.status() == status)
+                .count()
+        };
+        let (successes, flaky, errors, expected_failures, ignored) = (
+            count(TestStatus::Passed),
+            count(TestStatus::Flaky),
+            count(TestStatus::Failed),
+            count(TestStatus::ExpectedFailure),
+            count(TestStatus::Ignored),
+        );
+        if errors == 0 {
+            println!(
+                "\n{} tests ran with success; {} flaky; {} expected failures; {} ignored; \
+                 {} filtered out; finished in {:.2}s",
+                successes,
+                flaky,
+                expected_failures,
+                ignored,
+                summary.filtered_out,
+                summary.duration.as_secs_f64()
+            );
+        } else {
+            println!(
+                "\n{} errors; {} flaky; {} expected failures; {} ignored; {} filtered out; \
+                 finished in {:.2}s",
+                errors,
+                flaky,
+                expected_failures,
+                ignored,
+                summary.filtered_out,
+                summary.duration.as_secs_f64()
+            );
+        }
+    }
+
+    fn error(&mut self, error: &anyhow::Error) {
+        println!("{:#?}", error);
+    }
+}
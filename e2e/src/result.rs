@@ -0,0 +1,196 @@
+use crate::{
+    console::PageMessage,
+    device::{Device, Target},
+    timeout::TimedOut,
+    BrowserType,
+};
+use anyhow::Result;
+use std::{
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+pub struct TestResult {
+    pub test_name: &'static str,
+    pub browser_type: BrowserType,
+    /// The device the browser emulated, if the run has some
+    pub device: Option<Device>,
+    /// Why the test was not run, if it is ignored, it has no attempts then
+    pub ignored: Option<&'static str>,
+    /// The test failed in a browser it is known to fail in, which doesn't fail the run
+    pub expected_failure: bool,
+    /// Every attempt, in order, the last one decides whether the test passed
+    pub attempts: Vec<Attempt>,
+}
+
+pub struct Attempt {
+    pub outcome: TestOutcome,
+    /// What the test printed, on stdout or stderr, and its `log` and `tracing` records
+    pub output: Vec<u8>,
+    /// What the page logged to its console, and the errors it didn't catch
+    pub page_messages: Vec<PageMessage>,
+    /// Lines the server logged for the attempt, only kept when it failed
+    pub server_log: Vec<String>,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    /// Files saved while running the attempt, e.g. screenshots
+    pub artifacts: Vec<PathBuf>,
+}
+
+pub enum TestOutcome {
+    Passed,
+    Failed(anyhow::Error),
+    TimedOut(TimedOut),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    /// Passed, but only after failing at least once
+    Flaky,
+    Failed,
+    /// Failed in a browser it is known to fail in
+    ExpectedFailure,
+    /// Not run, see [`TestMeta::ignore`](crate::meta::TestMeta::ignore)
+    Ignored,
+}
+
+impl TestResult {
+    pub fn target(&self) -> Target {
+        Target {
+            browser_type: self.browser_type,
+            device: self.device,
+        }
+    }
+
+    pub fn status(&self) -> TestStatus {
+        if self.ignored.is_some() {
+            return TestStatus::Ignored;
+        }
+        if self.expected_failure {
+            return TestStatus::ExpectedFailure;
+        }
+        match self.attempts.last() {
+            Some(attempt) if attempt.outcome.is_success() => {
+                if self.attempts.len() == 1 {
+                    TestStatus::Passed
+                } else {
+                    TestStatus::Flaky
+                }
+            }
+            _ => TestStatus::Failed,
+        }
+    }
+
+    /// Time spent in the test, all attempts included
+    pub fn duration(&self) -> Duration {
+        self.attempts.iter().map(|attempt| attempt.duration).sum()
+    }
+
+    /// When the first attempt started
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.attempts.first().map(|attempt| attempt.started_at)
+    }
+
+    /// When the last attempt finished
+    pub fn finished_at(&self) -> Option<SystemTime> {
+        self.attempts.last().map(Attempt::finished_at)
+    }
+}
+
+impl Attempt {
+    pub fn finished_at(&self) -> SystemTime {
+        self.started_at + self.duration
+    }
+}
+
+impl TestOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, TestOutcome::Passed)
+    }
+}
+
+impl From<Result<()>> for TestOutcome {
+    fn from(result: Result<()>) -> Self {
+        match result {
+            Ok(()) => TestOutcome::Passed,
+            Err(err) => TestOutcome::Failed(err),
+        }
+    }
+}
+
+impl Display for TestResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = match (self.status(), self.attempts.last().map(|a| &a.outcome)) {
+            (TestStatus::Passed, _) => "OK",
+            (TestStatus::Flaky, _) => "FLAKY",
+            (TestStatus::Failed, Some(TestOutcome::TimedOut(_))) => "TIMEOUT",
+            (TestStatus::Failed, _) => "FAILED",
+            (TestStatus::ExpectedFailure, _) => "EXPECTED FAILURE",
+            (TestStatus::Ignored, _) => "IGNORED",
+        };
+        if let Some(reason) = self.ignored {
+            write!(
+                f,
+                "{} in {}...\t[{}]",
+                self.test_name,
+                self.target(),
+                status
+            )?;
+            if !reason.is_empty() {
+                write!(f, " {}", reason)?;
+            }
+            return Ok(());
+        }
+        write!(
+            f,
+            "{} in {}...\t[{}] {:.2}s",
+            self.test_name,
+            self.target(),
+            status,
+            self.duration().as_secs_f64()
+        )?;
+
+        for (index, attempt) in self.attempts.iter().enumerate() {
+            if self.attempts.len() > 1 {
+                write!(
+                    f,
+                    "\n   ----- ATTEMPT {}/{} -----   ",
+                    index + 1,
+                    self.attempts.len()
+                )?;
+            }
+            match &attempt.outcome {
+                TestOutcome::Passed => {}
+                TestOutcome::Failed(err) => write!(f, "\n{:#?}", err)?,
+                TestOutcome::TimedOut(timed_out) => write!(f, "\n{}", timed_out)?,
+            }
+            for artifact in &attempt.artifacts {
+                write!(f, "\nSaved {}", artifact.display())?;
+            }
+            if !attempt.output.is_empty() {
+                write!(
+                    f,
+                    "\n   ----- TEST OUTPUT -----   \n{}\n",
+                    String::from_utf8_lossy(&attempt.output)
+                )?;
+            }
+            if !attempt.page_messages.is_empty() {
+                write!(f, "\n   ----- BROWSER CONSOLE -----   ")?;
+                for message in &attempt.page_messages {
+                    write!(f, "\n{}", message)?;
+                }
+                writeln!(f)?;
+            }
+            if !attempt.server_log.is_empty() {
+                write!(f, "\n   ----- SERVER LOG -----   ")?;
+                for line in &attempt.server_log {
+                    write!(f, "\n{}", line)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
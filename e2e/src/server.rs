@@ -0,0 +1,671 @@
+use serde_derive::Deserialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt::{Debug, Display, Formatter},
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+pub use common::TEST_ID_HEADER;
+
+/// The server the tests are run against
+#[derive(Debug, Clone)]
+pub enum Server {
+    /// Launch the server before running the tests and kill it afterwards
+    Launch(ServerLauncher),
+    /// Don't launch anything, run the tests against an already running server at this base URL
+    External(String),
+}
+
+impl Server {
+    /// Reads the server configuration from a JSON file, either
+    ///
+    /// ```json
+    /// {
+    ///     "command": "target/release/web",
+    ///     "args": [],
+    ///     "env": { "ROCKET_LOG_LEVEL": "debug" },
+    ///     "working_dir": "..",
+    ///     "port": 8000,
+    ///     "port_env": "ROCKET_PORT",
+    ///     "health_path": "/",
+    ///     "timeout_secs": 30
+    /// }
+    /// ```
+    ///
+    /// where only `command` is required, or `{ "external_url": "https://staging.example.com" }`.
+    /// A relative `command` or `working_dir` is resolved from the directory of the file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ServerConfigError> {
+        let path = path.as_ref();
+        let error = |kind| ServerConfigError {
+            path: path.to_path_buf(),
+            kind,
+        };
+
+        let content = fs::read_to_string(path).map_err(|err| error(ConfigErrorKind::Io(err)))?;
+        let file: ServerConfigFile =
+            serde_json::from_str(&content).map_err(|err| error(ConfigErrorKind::Parse(err)))?;
+        // Absolute, as the command is spawned from the working directory
+        let base_dir = std::env::current_dir()
+            .map_err(|err| error(ConfigErrorKind::Io(err)))?
+            .join(path.parent().unwrap_or_else(|| Path::new("")));
+
+        match file {
+            ServerConfigFile {
+                external_url: Some(url),
+                command: None,
+                ..
+            } => Ok(Server::External(url)),
+            ServerConfigFile {
+                external_url: None,
+                command: Some(command),
+                args,
+                env,
+                working_dir,
+                port,
+                port_env,
+                health_path,
+                timeout_secs,
+            } => {
+                let command = if command.contains('/') {
+                    base_dir.join(command).into_os_string()
+                } else {
+                    command.into() // Looked up in PATH
+                };
+                let mut launcher = ServerLauncher::new(command)
+                    .args(args)
+                    .current_dir(base_dir.join(working_dir.unwrap_or_default()));
+                for (key, value) in env {
+                    launcher = launcher.env(key, value);
+                }
+                if let Some(port) = port {
+                    launcher = launcher.port(port);
+                }
+                if let Some(port_env) = port_env {
+                    launcher = launcher.port_env(port_env);
+                }
+                if let Some(health_path) = health_path {
+                    launcher = launcher.health_path(health_path);
+                }
+                if let Some(timeout_secs) = timeout_secs {
+                    let timeout = Duration::try_from_secs_f64(timeout_secs)
+                        .map_err(|_| error(ConfigErrorKind::InvalidTimeout(timeout_secs)))?;
+                    launcher = launcher.timeout(timeout);
+                }
+                Ok(Server::Launch(launcher))
+            }
+            _ => Err(error(ConfigErrorKind::CommandOrExternalUrl)),
+        }
+    }
+}
+
+impl Default for Server {
+    /// The `web` binary of this workspace, built in debug mode
+    fn default() -> Self {
+        Server::Launch(ServerLauncher::new("target/debug/web").current_dir(".."))
+    }
+}
+
+/// Describes how to launch the server under test
+///
+/// Unless a fixed [`port`](Self::port) is given, a free port is picked at launch so several
+/// suites can run side by side. It is passed to the server in the `ROCKET_PORT` environment
+/// variable (see [`port_env`](Self::port_env)) and replaces `{port}` in its arguments.
+///
+/// ```no_run
+/// # use e2e::server::ServerLauncher;
+/// # use std::time::Duration;
+/// let launcher = ServerLauncher::new("target/release/web")
+///     .current_dir("..")
+///     .env("ROCKET_LOG_LEVEL", "debug")
+///     .port(8000)
+///     .health_path("/")
+///     .timeout(Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerLauncher {
+    program: OsString,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    current_dir: Option<PathBuf>,
+    port: Option<u16>,
+    port_env: String,
+    health_path: Option<String>,
+    timeout: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl ServerLauncher {
+    pub fn new<S: Into<OsString>>(program: S) -> Self {
+        ServerLauncher {
+            program: program.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: None,
+            port: None,
+            port_env: "ROCKET_PORT".to_string(),
+            health_path: None,
+            timeout: Duration::from_secs(30),
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    pub fn arg<S: Into<String>>(mut self, arg: S) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Port the server listens on, on `127.0.0.1`, instead of a free one
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Environment variable the port is passed in, `ROCKET_PORT` by default
+    pub fn port_env<S: Into<String>>(mut self, name: S) -> Self {
+        self.port_env = name.into();
+        self
+    }
+
+    /// Wait for a `GET` on this path to succeed instead of waiting for the port to accept
+    /// connections
+    pub fn health_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.health_path = Some(path.into());
+        self
+    }
+
+    /// How long to wait for the server to be ready
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Delays between readiness checks, doubled after each failed check up to `max`
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn program(&self) -> &OsStr {
+        &self.program
+    }
+
+    pub fn launch(&self) -> io::Result<WebServer> {
+        let port = match self.port {
+            Some(port) => port,
+            None => free_port()?,
+        };
+        let base_url = format!("http://127.0.0.1:{}", port);
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(
+                self.args
+                    .iter()
+                    .map(|arg| arg.replace("{port}", &port.to_string())),
+            )
+            .env(&self.port_env, port.to_string())
+            .envs(self.env.iter().cloned());
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+
+        let probe = match &self.health_path {
+            Some(path) => Probe::Http(format!("{}/{}", base_url, path.trim_start_matches('/'))),
+            None => Probe::Tcp(SocketAddr::from(([127, 0, 0, 1], port))),
+        };
+        let readiness = Readiness {
+            probe,
+            timeout: self.timeout,
+            initial_backoff: self.initial_backoff,
+            max_backoff: self.max_backoff,
+        };
+
+        WebServer::spawn(&mut command, base_url, readiness)
+    }
+}
+
+/// Asks the OS for a port nobody listens on. It is released right away so the server can bind
+/// it, which leaves a small window for another process to take it.
+fn free_port() -> io::Result<u16> {
+    Ok(TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerConfigFile {
+    external_url: Option<String>,
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    working_dir: Option<PathBuf>,
+    port: Option<u16>,
+    port_env: Option<String>,
+    health_path: Option<String>,
+    timeout_secs: Option<f64>,
+}
+
+/// A launched web server
+pub struct WebServer {
+    child: Child,
+    log: ServerLog,
+    drains: Vec<JoinHandle<()>>,
+    base_url: String,
+    readiness: Readiness,
+}
+
+impl WebServer {
+    /// Spawns the server, its output is captured so it can be reported if it fails to start or
+    /// when tests fail
+    fn spawn(command: &mut Command, base_url: String, readiness: Readiness) -> io::Result<Self> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let log = ServerLog::default();
+        let mut drains = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            drains.push(log.drain(stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            drains.push(log.drain(stderr));
+        }
+
+        Ok(WebServer {
+            child,
+            log,
+            drains,
+            base_url,
+            readiness,
+        })
+    }
+
+    /// URL the tests should send their requests to, e.g. `http://127.0.0.1:38279`
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Polls the server with its readiness probe until it answers, backing off exponentially
+    /// between attempts
+    pub async fn wait_until_ready(&mut self) -> Result<(), ServerNotReady> {
+        let readiness = self.readiness.clone();
+        let start = Instant::now();
+        let mut backoff = readiness.initial_backoff;
+
+        loop {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                // The pipes are closed, wait for the draining threads to catch the last lines
+                for drain in self.drains.drain(..) {
+                    let _ = drain.join();
+                }
+                return Err(self.not_ready(NotReadyReason::Exited(status)));
+            }
+            // A check still waiting for an answer at the deadline counts as not ready
+            let remaining = readiness.timeout.saturating_sub(start.elapsed());
+            if let Ok(true) = tokio::time::timeout(remaining, readiness.probe.check()).await {
+                return Ok(());
+            }
+            if start.elapsed() >= readiness.timeout {
+                return Err(self.not_ready(NotReadyReason::Timeout(readiness.timeout)));
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(readiness.max_backoff);
+        }
+    }
+
+    /// Output of the server so far
+    pub fn log(&self) -> &ServerLog {
+        &self.log
+    }
+
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+
+    fn not_ready(&self, reason: NotReadyReason) -> ServerNotReady {
+        let lines = self.log.0.lock().unwrap();
+        ServerNotReady {
+            reason,
+            output: lines
+                .iter()
+                .map(|line| format!("{}\n", line.text))
+                .collect(),
+        }
+    }
+}
+
+/// Output of a server, stdout and stderr interleaved, line by line
+#[derive(Clone, Default)]
+pub struct ServerLog(Arc<Mutex<Vec<LogLine>>>);
+
+pub struct LogLine {
+    /// When the line was read, shortly after it was written
+    pub time: SystemTime,
+    /// Without its line break nor terminal escape codes
+    pub text: String,
+}
+
+impl ServerLog {
+    /// Appends the lines of `pipe` on a thread of its own. The pipe has to be drained
+    /// continuously, otherwise the server blocks once it is full.
+    fn drain<R: Read + Send + 'static>(&self, pipe: R) -> JoinHandle<()> {
+        let lines = Arc::clone(&self.0);
+        thread::spawn(move || {
+            let mut pipe = BufReader::new(pipe);
+            let mut line = Vec::new();
+            while let Ok(1..) = pipe.read_until(b'\n', &mut line) {
+                let text = strip_escape_codes(&String::from_utf8_lossy(&line));
+                lines.lock().unwrap().push(LogLine {
+                    time: SystemTime::now(),
+                    text: text.trim_end_matches(&['\r', '\n'][..]).to_string(),
+                });
+                line.clear();
+            }
+        })
+    }
+
+    /// The lines of the attempt of a test identified by `test_id` (see [`TEST_ID_HEADER`]) that
+    /// ran from `start` to `end`
+    pub fn lines_of_test(&self, test_id: &str, start: SystemTime, end: SystemTime) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| {
+                if line.text.contains(TEST_ID_HEADER) {
+                    mentions(&line.text, test_id)
+                } else {
+                    (start..=end).contains(&line.time)
+                }
+            })
+            .map(|line| line.text.clone())
+            .collect()
+    }
+}
+
+/// Whether `text` contains `id` as a whole, e.g. `test#1` is not mentioned by `test#12`, nor
+/// `login@chromium#1` by `tests::login@chromium#1`
+fn mentions(text: &str, id: &str) -> bool {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(id).any(|(index, _)| {
+        !text[..index].ends_with(|c: char| is_identifier(c) || c == ':')
+            && !text[index + id.len()..].starts_with(is_identifier)
+    })
+}
+
+/// Removes the ANSI escape sequences servers use to color their output
+fn strip_escape_codes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Control sequences end with a letter, e.g. `\x1b[1;32m`
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// How to tell the server is ready to accept requests
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    pub probe: Probe,
+    /// Give up after this long
+    pub timeout: Duration,
+    /// Delay before the second attempt, doubled after each failed attempt
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts
+    pub max_backoff: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum Probe {
+    /// Ready once a TCP connection can be established
+    Tcp(SocketAddr),
+    /// Ready once a `GET` to this `http://` URL answers with a 2xx or 3xx status
+    Http(String),
+}
+
+impl Probe {
+    async fn check(&self) -> bool {
+        match self {
+            Probe::Tcp(addr) => TcpStream::connect(addr).await.is_ok(),
+            Probe::Http(url) => http_get_succeeds(url).await.unwrap_or(false),
+        }
+    }
+}
+
+async fn http_get_succeeds(url: &str) -> io::Result<bool> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only http:// URLs are supported",
+        )
+    })?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+
+    let mut stream = TcpStream::connect(address).await?;
+    stream
+        .write_all(
+            format!(
+                "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                path, host
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    let mut status_line = [0; 12];
+    stream.read_exact(&mut status_line).await?;
+    // "HTTP/1.1 200"
+    Ok(matches!(status_line[9], b'2' | b'3'))
+}
+
+#[derive(Debug)]
+pub enum NotReadyReason {
+    Timeout(Duration),
+    Exited(ExitStatus),
+}
+
+pub struct ServerNotReady {
+    pub reason: NotReadyReason,
+    pub output: String,
+}
+
+impl Display for ServerNotReady {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.reason {
+            NotReadyReason::Timeout(timeout) => write!(
+                f,
+                "Web server was not ready after {:.1}s",
+                timeout.as_secs_f32()
+            )?,
+            NotReadyReason::Exited(status) => {
+                write!(f, "Web server exited before being ready ({})", status)?
+            }
+        }
+        if !self.output.is_empty() {
+            write!(f, "\n   ----- SERVER OUTPUT -----   \n{}", self.output)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for ServerNotReady {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for ServerNotReady {}
+
+#[derive(Debug)]
+pub struct ServerConfigError {
+    pub path: PathBuf,
+    pub kind: ConfigErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ConfigErrorKind {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    CommandOrExternalUrl,
+    /// `timeout_secs` is negative or too large
+    InvalidTimeout(f64),
+}
+
+impl Display for ServerConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid server configuration {}: ", self.path.display())?;
+        match &self.kind {
+            ConfigErrorKind::Io(err) => write!(f, "{}", err),
+            ConfigErrorKind::Parse(err) => write!(f, "{}", err),
+            ConfigErrorKind::CommandOrExternalUrl => {
+                write!(f, "exactly one of `command` or `external_url` must be set")
+            }
+            ConfigErrorKind::InvalidTimeout(timeout_secs) => {
+                write!(f, "invalid `timeout_secs` {}", timeout_secs)
+            }
+        }
+    }
+}
+
+impl Error for ServerConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ConfigErrorKind::Io(err) => Some(err),
+            ConfigErrorKind::Parse(err) => Some(err),
+            ConfigErrorKind::CommandOrExternalUrl | ConfigErrorKind::InvalidTimeout(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_whole_ids() {
+        assert!(mentions(
+            "GET / x-e2e-test-id: login@chromium#1",
+            "login@chromium#1"
+        ));
+        assert!(mentions(
+            "id=login@chromium#1, status=200",
+            "login@chromium#1"
+        ));
+        assert!(!mentions(
+            "x-e2e-test-id: login@chromium#12",
+            "login@chromium#1"
+        ));
+        assert!(!mentions(
+            "x-e2e-test-id: tests::login@chromium#1",
+            "login@chromium#1"
+        ));
+        assert!(!mentions(
+            "x-e2e-test-id: admin_login@chromium#1",
+            "login@chromium#1"
+        ));
+    }
+
+    #[test]
+    fn strips_escape_codes() {
+        assert_eq!(
+            strip_escape_codes("\u{1b}[1;32mGET\u{1b}[0m / 200"),
+            "GET / 200"
+        );
+        assert_eq!(strip_escape_codes("no codes"), "no codes");
+    }
+
+    #[test]
+    fn lines_of_test() {
+        let log = ServerLog::default();
+        let start = SystemTime::now();
+        log.drain(
+            &b"\x1b[32mstarted\x1b[0m\r\nGET /a x-e2e-test-id: a#1\nGET /b x-e2e-test-id: b#1\n"[..],
+        )
+        .join()
+        .unwrap();
+        let end = SystemTime::now();
+
+        assert_eq!(
+            log.lines_of_test("a#1", start, end),
+            ["started", "GET /a x-e2e-test-id: a#1"]
+        );
+        assert_eq!(
+            log.lines_of_test(
+                "b#1",
+                end + Duration::from_secs(1),
+                end + Duration::from_secs(2)
+            ),
+            ["GET /b x-e2e-test-id: b#1"]
+        );
+    }
+
+    #[test]
+    fn invalid_timeout() {
+        let path = std::env::temp_dir().join(format!("e2e-server-{}.json", std::process::id()));
+        fs::write(&path, r#"{ "command": "./web", "timeout_secs": -1 }"#).unwrap();
+        let result = Server::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(ServerConfigError {
+                kind: ConfigErrorKind::InvalidTimeout(_),
+                ..
+            })
+        ));
+    }
+}
@@ -1,2 +0,0 @@
-pub mod fetch;
-pub mod hello_world1;
@@ -0,0 +1,230 @@
+use futures::future::{self, Either};
+use pin_project::pin_project;
+use std::{
+    cell::RefCell,
+    error::Error,
+    fmt::{Debug, Display, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// Awaits `future`, reporting `label` as pending if the test times out before it completes
+///
+/// [`Context::goto`](crate::Context::goto) and
+/// [`PageFetchExt`](crate::playwright_ext::PageFetchExt) already label their awaits.
+pub async fn labelled<F: Future, S: Into<String>>(label: S, future: F) -> F::Output {
+    let _guard = PendingGuard::enter(label.into());
+    future.await
+}
+
+/// Awaits `future` for at most `timeout`, failing with [`TimedOut`] once it is reached, used for
+/// what runs after a test, which its own timeout doesn't cover
+pub(crate) async fn bounded<T, F>(timeout: Duration, future: F) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    let pending = PendingAwaits::default();
+    let deadline = Deadline::new(timeout);
+    let completed = future::select(
+        Box::pin(pending.track(future)),
+        Box::pin(deadline.elapsed()),
+    )
+    .await;
+    match completed {
+        Either::Left((result, _)) => result,
+        Either::Right(((), _)) => Err(TimedOut {
+            timeout,
+            pending: pending.labels(),
+        }
+        .into()),
+    }
+}
+
+/// Deadline of a test, shared between the runner and the [`Context`](crate::Context) so the
+/// test can change it while it runs
+#[derive(Clone)]
+pub(crate) struct Deadline {
+    started: Instant,
+    timeout: Arc<Mutex<Duration>>,
+}
+
+impl Deadline {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Deadline {
+            started: Instant::now(),
+            timeout: Arc::new(Mutex::new(timeout)),
+        }
+    }
+
+    pub(crate) fn timeout(&self) -> Duration {
+        *self.timeout.lock().unwrap()
+    }
+
+    pub(crate) fn set_timeout(&self, timeout: Duration) {
+        *self.timeout.lock().unwrap() = timeout;
+    }
+
+    /// Completes once the deadline is reached, taking into account changes made meanwhile
+    pub(crate) async fn elapsed(&self) {
+        loop {
+            // A deadline too far away to be represented is never reached
+            let remaining = match self.started.checked_add(self.timeout()) {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => Duration::MAX,
+            };
+            if remaining.is_zero() {
+                return;
+            }
+            // Capped so that the timer doesn't overflow either
+            tokio::time::sleep(remaining.min(Duration::from_secs(3600))).await;
+        }
+    }
+}
+
+/// Labels of the awaits a test is currently pending on
+#[derive(Clone, Default)]
+pub(crate) struct PendingAwaits(Arc<Mutex<Vec<(u64, String)>>>);
+
+thread_local! {
+    static CURRENT: RefCell<Option<PendingAwaits>> = const { RefCell::new(None) };
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+impl PendingAwaits {
+    /// Makes `future` record its labelled awaits into `self`
+    pub(crate) fn track<Fut>(&self, future: Fut) -> TrackPending<Fut> {
+        TrackPending {
+            future,
+            pending: self.clone(),
+        }
+    }
+
+    pub(crate) fn labels(&self) -> Vec<String> {
+        let pending = self.0.lock().unwrap();
+        pending.iter().map(|(_, label)| label.clone()).collect()
+    }
+}
+
+#[pin_project]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub(crate) struct TrackPending<Fut> {
+    #[pin]
+    future: Fut,
+    pending: PendingAwaits,
+}
+
+impl<Fut: Future> Future for TrackPending<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let previous = CURRENT.with(|current| current.replace(Some(this.pending.clone())));
+        let result = this.future.poll(cx);
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+        result
+    }
+}
+
+/// Reports a label as pending until dropped, see [`labelled`]
+pub(crate) struct PendingGuard {
+    pending: Option<PendingAwaits>,
+    id: u64,
+}
+
+impl PendingGuard {
+    pub(crate) fn enter(label: String) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let pending = CURRENT.with(|current| current.borrow().clone());
+        if let Some(pending) = &pending {
+            pending.0.lock().unwrap().push((id, label));
+        }
+        PendingGuard { pending, id }
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if let Some(pending) = &self.pending {
+            pending.0.lock().unwrap().retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+pub struct TimedOut {
+    pub timeout: Duration,
+    /// Labels of the awaits that were pending when the test timed out
+    pub pending: Vec<String>,
+}
+
+impl Display for TimedOut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timed out after {:.1}s", self.timeout.as_secs_f32())?;
+        if self.pending.is_empty() {
+            write!(f, " (no labelled await pending)")
+        } else {
+            write!(f, " while awaiting:")?;
+            for label in &self.pending {
+                write!(f, "\n\t- {}", label)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Debug for TimedOut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Error for TimedOut {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn times_out_with_pending_labels() {
+        let result = block_on(bounded(Duration::from_millis(10), async {
+            labelled("sleep", tokio::time::sleep(Duration::from_secs(60))).await;
+            Ok(())
+        }));
+        let timed_out = result.unwrap_err().downcast::<TimedOut>().unwrap();
+        assert_eq!(timed_out.pending, ["sleep"]);
+        assert_eq!(
+            timed_out.to_string(),
+            "Timed out after 0.0s while awaiting:\n\t- sleep"
+        );
+    }
+
+    #[test]
+    fn completes_before_the_timeout() {
+        let result = block_on(bounded(Duration::MAX, async { Ok(42) }));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn unrepresentable_deadline_is_never_reached() {
+        let deadline = Deadline::new(Duration::MAX);
+        let completed = block_on(async {
+            let sleep = tokio::time::sleep(Duration::from_millis(10));
+            future::select(Box::pin(deadline.elapsed()), Box::pin(sleep)).await
+        });
+        assert!(matches!(completed, Either::Right(_)));
+    }
+}
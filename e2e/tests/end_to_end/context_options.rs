@@ -0,0 +1,47 @@
+use anyhow::Result;
+use e2e::Context;
+
+#[e2e::test(
+    locale = "fr-FR",
+    timezone = "Europe/Paris",
+    viewport = "375x812",
+    color_scheme = "dark"
+)]
+async fn localized_mobile(ctx: Context) -> Result<()> {
+    ctx.goto("/").await?;
+
+    let language: String = ctx.page.eval("() => navigator.language").await?;
+    assert_eq!(language, "fr-FR");
+    let timezone: String = ctx
+        .page
+        .eval("() => Intl.DateTimeFormat().resolvedOptions().timeZone")
+        .await?;
+    assert_eq!(timezone, "Europe/Paris");
+    let width: i32 = ctx.page.eval("() => window.innerWidth").await?;
+    assert_eq!(width, 375);
+    let dark: bool = ctx
+        .page
+        .eval("() => matchMedia('(prefers-color-scheme: dark)').matches")
+        .await?;
+    assert!(dark);
+
+    Ok(())
+}
+
+#[e2e::test(permissions = ["geolocation"], geolocation = [48.8566, 2.3522])]
+async fn geolocation(ctx: Context) -> Result<()> {
+    ctx.goto("/").await?;
+
+    let latitude: f64 = ctx
+        .page
+        .eval(
+            "() => new Promise((resolve, reject) => navigator.geolocation.getCurrentPosition(
+                position => resolve(position.coords.latitude),
+                reject,
+            ))",
+        )
+        .await?;
+    assert_eq!(latitude, 48.8566);
+
+    Ok(())
+}
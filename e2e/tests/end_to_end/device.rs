@@ -0,0 +1,12 @@
+use anyhow::Result;
+use e2e::Context;
+
+#[e2e::test]
+async fn touch(ctx: Context) -> Result<()> {
+    ctx.goto("/").await?;
+
+    let touch: bool = ctx.page.eval("() => navigator.maxTouchPoints > 0").await?;
+    assert_eq!(touch, ctx.device().is_some_and(|device| device.has_touch));
+
+    Ok(())
+}
@@ -1,14 +1,11 @@
-use crate::{playwright_ext::PageFetchExt, Context};
 use anyhow::Result;
 use common::PayloadCookies;
+use e2e::{playwright_ext::PageFetchExt, Context};
 use futures::try_join;
 
-#[test_case]
+#[e2e::test]
 async fn get_404(ctx: Context) -> Result<()> {
-    ctx.page
-        .goto_builder("http://127.0.0.1:8000")
-        .goto()
-        .await?;
+    ctx.goto("/").await?;
 
     let response_404 = ctx.page.get("/404").await?;
 
@@ -17,12 +14,9 @@ async fn get_404(ctx: Context) -> Result<()> {
     Ok(())
 }
 
-#[test_case]
+#[e2e::test]
 async fn mixed_methods(ctx: Context) -> Result<()> {
-    ctx.page
-        .goto_builder("http://127.0.0.1:8000")
-        .goto()
-        .await?;
+    ctx.goto("/").await?;
 
     let (get, post, put, patch, delete) = try_join!(
         ctx.page.get("/check-cookies"),
@@ -0,0 +1,30 @@
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use e2e::{
+    fixture::{Fixture, FixtureContext},
+    playwright_ext::PageFetchExt,
+    Context,
+};
+
+/// The index was visited, which sets the cookies `/check-cookies` expects
+struct Visited;
+
+#[async_trait]
+impl Fixture for Visited {
+    async fn setup(ctx: &FixtureContext<'_>) -> Result<Self> {
+        let page = ctx.page().context("test fixture without a page")?;
+        page.goto_builder(&ctx.url("/")).goto().await?;
+        Ok(Visited)
+    }
+}
+
+#[e2e::test(fixtures = [Visited])]
+async fn check_cookies(ctx: Context) -> Result<()> {
+    ctx.fixture::<Visited>()?;
+
+    let response = ctx.page.get("/check-cookies").await?;
+
+    assert_eq!(response.status()?, 200);
+
+    Ok(())
+}
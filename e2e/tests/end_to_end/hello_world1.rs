@@ -1,12 +1,9 @@
-use crate::Context;
 use anyhow::Result;
+use e2e::Context;
 
-#[test_case]
+#[e2e::test]
 async fn hello_world1(ctx: Context) -> Result<()> {
-    ctx.page
-        .goto_builder("http://127.0.0.1:8000")
-        .goto()
-        .await?;
+    ctx.goto("/").await?;
     let body = ctx.page.inner_text("body", None).await?;
     assert_eq!(body, "Hello, world!");
 
@@ -0,0 +1,17 @@
+use anyhow::{ensure, Context as _, Result};
+use e2e::fixture::FixtureContext;
+
+#[e2e::before_all(per_browser)]
+async fn browser_version(ctx: &FixtureContext<'_>) -> Result<()> {
+    let browser = ctx
+        .browser()
+        .context("per browser hook without a browser")?;
+    let version = browser.version()?;
+    ensure!(
+        !version.is_empty(),
+        "{:?} has no version",
+        ctx.browser_type()
+    );
+
+    Ok(())
+}
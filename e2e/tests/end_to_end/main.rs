@@ -0,0 +1,45 @@
+//! The end-to-end tests of the runner itself, against the `web` binary of this workspace. They
+//! run on stable Rust, without `custom_test_frameworks`.
+
+mod context_options;
+mod device;
+mod fetch;
+mod fixtures;
+mod hello_world1;
+mod hooks;
+mod meta;
+
+use anyhow::Result;
+use e2e::Context;
+use std::time::Duration;
+
+#[e2e::test(tags = ["smoke"])]
+async fn hello_world(ctx: Context) -> Result<()> {
+    ctx.goto("/").await?;
+    let body = ctx.page.inner_text("body", None).await?;
+    assert_eq!(body, "Hello, world!");
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn sleep(_ctx: Context) -> Result<()> {
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    Ok(())
+}
+
+// #[e2e::test]
+// async fn err(_ctx: Context) -> Result<()> {
+//     use std::str::FromStr;
+//     let _ = i32::from_str("Not a number")?;
+//     Ok(())
+// }
+//
+// #[e2e::test]
+// async fn unimplemented(_ctx: Context) -> Result<()> {
+//     unimplemented!()
+// }
+
+fn main() {
+    e2e::e2e_main()
+}
@@ -0,0 +1,11 @@
+use anyhow::Result;
+use e2e::Context;
+
+#[e2e::test(browsers = [chromium], timeout = "10s", tags = ["smoke"])]
+async fn user_agent(ctx: Context) -> Result<()> {
+    ctx.goto("/").await?;
+    let user_agent: String = ctx.page.eval("() => navigator.userAgent").await?;
+    assert!(user_agent.contains("Chrome"), "{}", user_agent);
+
+    Ok(())
+}
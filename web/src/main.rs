@@ -1,12 +1,52 @@
 #[macro_use]
 extern crate rocket;
 
-use common::PayloadCookies;
+use common::{PayloadCookies, TEST_ID_HEADER};
 use rocket::{
+    fairing::{Fairing, Info, Kind},
     http::{Cookie, CookieJar},
     serde::json::Json,
+    Data, Request, Response,
 };
 
+/// Logs the test that sent each request, so the e2e runner can attach our log to its failures
+struct LogTestId;
+
+#[rocket::async_trait]
+impl Fairing for LogTestId {
+    fn info(&self) -> Info {
+        Info {
+            name: "Log e2e test id",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        if let Some(test_id) = req.headers().get_one(TEST_ID_HEADER) {
+            println!(
+                "{}: {} {} {}",
+                TEST_ID_HEADER,
+                test_id,
+                req.method(),
+                req.uri()
+            );
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if let Some(test_id) = req.headers().get_one(TEST_ID_HEADER) {
+            println!(
+                "{}: {} {} {} => {}",
+                TEST_ID_HEADER,
+                test_id,
+                req.method(),
+                req.uri(),
+                res.status()
+            );
+        }
+    }
+}
+
 #[get("/")]
 fn index(cookies: &CookieJar) -> &'static str {
     cookies.add(Cookie::new("Response", "42"));
@@ -87,7 +127,7 @@ fn delete_check_cookies(cookies: &CookieJar) -> &'static str {
 
 #[launch]
 fn rocket() -> _ {
-    rocket::build().mount(
+    rocket::build().attach(LogTestId).mount(
         "/",
         routes![
             index,